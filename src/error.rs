@@ -10,3 +10,21 @@ pub enum PosyError {
     #[error("remote file does not support range requests")]
     LazyRemoteFileNotSupported,
 }
+
+/// Errors from the `vocab` parsers, for callers that need to tell them apart. They're
+/// still raised as `eyre::Report`s, same as everything else; use
+/// `err.downcast_ref::<VocabError>()` to get at them.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum VocabError {
+    #[error("Failed to parse PEP 440 version {input}")]
+    InvalidVersion { input: String },
+    #[error("invalid version specifier {input:?}")]
+    InvalidSpecifier { input: String },
+    #[error(
+        "unrecognized version comparison operator {op:?} (expected one of \
+         <=, <, !=, ==, >=, >, ~=, ===)"
+    )]
+    UnknownOperator { op: String },
+    #[error("Failed parsing env marker expression {input:?}")]
+    InvalidMarker { input: String },
+}
@@ -210,6 +210,7 @@ impl<'a> WheelBuilder<'a> {
                     name: self.target_python.clone(),
                     extras: Default::default(),
                     specifiers: Default::default(),
+                    url: Default::default(),
                     env_marker_expr: Default::default(),
                 })
                 .unwrap(),
@@ -246,11 +247,12 @@ impl<'a> WheelBuilder<'a> {
             // Ideally, we can find a Python that's an exact match to the target python.
             PythonRequirement::try_from(Requirement {
                 name: self.target_python.clone(),
-                specifiers: Specifiers(vec![Specifier {
-                    op: CompareOp::Equal,
-                    value: self.target_python_version.to_string(),
-                }]),
+                specifiers: Specifiers(vec![Specifier::new(
+                    CompareOp::Equal,
+                    &self.target_python_version.to_string(),
+                )?]),
                 extras: Default::default(),
+                url: Default::default(),
                 env_marker_expr: Default::default(),
             })
             .unwrap(),
@@ -258,11 +260,12 @@ impl<'a> WheelBuilder<'a> {
             // CPython at least, the C ABI is stable within a minor release).
             PythonRequirement::try_from(Requirement {
                 name: self.target_python.clone(),
-                specifiers: Specifiers(vec![Specifier {
-                    op: CompareOp::Equal,
-                    value: format!("{}.*", same_minor),
-                }]),
+                specifiers: Specifiers(vec![Specifier::new(
+                    CompareOp::Equal,
+                    &format!("{}.*", same_minor),
+                )?]),
                 extras: Default::default(),
+                url: Default::default(),
                 env_marker_expr: Default::default(),
             })
             .unwrap(),
@@ -272,6 +275,7 @@ impl<'a> WheelBuilder<'a> {
                 name: self.target_python.clone(),
                 extras: Default::default(),
                 specifiers: Default::default(),
+                url: Default::default(),
                 env_marker_expr: Default::default(),
             })
             .unwrap(),
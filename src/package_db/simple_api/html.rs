@@ -84,6 +84,10 @@ impl Sink {
     ) -> Option<Vec<ArtifactInfo>> {
         let url = self.base.join(url_str).ok()?;
         let name: ArtifactName = url.path_segments()?.next_back()?.try_into().ok()?;
+        // We can recognize eggs, but not install them, so pretend they aren't there
+        if let ArtifactName::Egg(_) = name {
+            return None;
+        }
         let names = name.split_multiplatform_pybis();
         // We found a valid link
         let hash = url.fragment().and_then(parse_hash);
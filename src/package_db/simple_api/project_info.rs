@@ -1,6 +1,7 @@
 use crate::prelude::*;
 
 use indexmap::IndexMap;
+use serde_with::{serde_as, DisplayFromStr};
 
 // Generic structs representing the information carried a simple Simple API response
 // body for a single project, whether using HTML (PEP 503) or JSON (PEP 691). But it's
@@ -29,11 +30,14 @@ enum RawDistInfoMetadata {
     WithHashes(HashMap<String, String>),
 }
 
+// (vocab types go through DisplayFromStr, since their own serde impls are optional)
+#[serde_as]
 #[derive(Debug, Clone, Deserialize, Default, PartialEq, Eq, Serialize)]
 #[serde(from = "Option<RawDistInfoMetadata>")]
 pub struct DistInfoMetadata {
     pub available: bool,
     // TODO: support multiple hashes here too
+    #[serde_as(as = "Option<DisplayFromStr>")]
     pub hash: Option<ArtifactHash>,
 }
 
@@ -88,9 +92,11 @@ impl From<RawYanked> for Yanked {
     }
 }
 
+#[serde_as]
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 //#[serde(rename_all = "kebab-case")]
 pub struct ArtifactInfo {
+    #[serde_as(as = "DisplayFromStr")]
     pub name: ArtifactName,
     pub url: Url,
     // TODO: the json api allows this to be a map of algorithm->hex string, with
@@ -99,6 +105,7 @@ pub struct ArtifactInfo {
     // implementation warehouse's json API only has one hash, and supporting multiple
     // hashes raises design questions for our caching strategy and lockfiles so... meh
     // just gonna make that future-me's problem...
+    #[serde_as(as = "Option<DisplayFromStr>")]
     pub hash: Option<ArtifactHash>,
     pub requires_python: Option<String>,
     //    #[serde(default)]
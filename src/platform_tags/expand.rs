@@ -91,10 +91,68 @@ pub fn expand_platform_tag(tag: &str) -> Vec<String> {
     vec![tag.to_string()]
 }
 
+// The manylinux tags that a host with the given glibc version and architecture can
+// run, best first: every manylinux_{major}_{minor} with the same major version and a
+// lower-or-equal minor version, with the legacy manylinux1/2010/2014 aliases slotted
+// in next to their glibc equivalents.
+pub fn manylinux_tags(glibc_version: (u32, u32), arch: &str) -> Vec<String> {
+    let (major, minor) = glibc_version;
+    expand_platform_tag(&format!("manylinux_{major}_{minor}_{arch}"))
+}
+
+// Same as manylinux_tags, but for musl-based hosts. There are no legacy aliases here.
+pub fn musllinux_tags(musl_version: (u32, u32), arch: &str) -> Vec<String> {
+    let (major, minor) = musl_version;
+    expand_platform_tag(&format!("musllinux_{major}_{minor}_{arch}"))
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
+    #[test]
+    fn test_manylinux_tags() {
+        let tags = manylinux_tags((2, 28), "x86_64");
+        assert_eq!(tags[0], "manylinux_2_28_x86_64");
+        assert_eq!(tags.last().unwrap(), "manylinux_2_0_x86_64");
+        assert!(tags.contains(&"manylinux_2_17_x86_64".to_string()));
+        assert!(!tags.contains(&"manylinux_2_29_x86_64".to_string()));
+
+        // legacy aliases come right after their modern spelling
+        let pos = |tag: &str| tags.iter().position(|t| t == tag).unwrap();
+        assert_eq!(
+            pos("manylinux2014_x86_64"),
+            pos("manylinux_2_17_x86_64") + 1
+        );
+        assert_eq!(
+            pos("manylinux2010_x86_64"),
+            pos("manylinux_2_12_x86_64") + 1
+        );
+        assert_eq!(pos("manylinux1_x86_64"), pos("manylinux_2_5_x86_64") + 1);
+
+        // too old for manylinux2010
+        let old = manylinux_tags((2, 11), "x86_64");
+        assert!(old.contains(&"manylinux1_x86_64".to_string()));
+        assert!(!old.contains(&"manylinux2010_x86_64".to_string()));
+
+        // no cross-arch compatibility
+        assert!(tags.iter().all(|t| t.ends_with("_x86_64")));
+        assert!(!manylinux_tags((2, 28), "aarch64")
+            .contains(&"manylinux_2_17_x86_64".to_string()));
+    }
+
+    #[test]
+    fn test_musllinux_tags() {
+        assert_eq!(
+            musllinux_tags((1, 2), "aarch64"),
+            vec![
+                "musllinux_1_2_aarch64",
+                "musllinux_1_1_aarch64",
+                "musllinux_1_0_aarch64",
+            ]
+        );
+    }
+
     #[test]
     fn test_expand_platform_tag() {
         insta::assert_ron_snapshot!(expand_platform_tag("win32"), @r###"
@@ -2,8 +2,9 @@
 /// .pybi's it will be able to run. This is similar to what 'packaging.tags' does, but
 /// with two key differences:
 ///
-/// - We only care about "platform" tags (like "win32"), not the python-specific tags
-///   (like "cp37")
+/// - We mostly care about "platform" tags (like "win32"), not the python-specific tags
+///   (like "cp37"). The exception is `python_tags::compatible_tags`, which combines
+///   the two for picking wheels for a known interpreter.
 ///
 /// - For 'packaging.tags', the question is "what wheels can run on this interpreter
 ///   that I've already installed?" So like, if it's running on a 64-bit interpreter, it
@@ -32,4 +33,5 @@ use macos::core_platform_tags;
 
 mod expand;
 mod platform;
+pub mod python_tags;
 pub use platform::{Platform, PybiPlatform, WheelPlatform};
@@ -0,0 +1,247 @@
+use super::expand::expand_platform_tag;
+
+/// The facts about an interpreter that decide which wheels it can install. This is
+/// the same information 'packaging.tags' gets by introspecting the running Python.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterpreterInfo {
+    /// The implementation's abbreviation in wheel tags: "cp" for CPython, "pp" for
+    /// PyPy, etc.
+    pub implementation: String,
+    /// (major, minor), e.g. (3, 11)
+    pub python_version: (u32, u32),
+    /// The interpreter's own ABI tag, like "cp311" or "pypy39_pp73", if it has one.
+    pub abi: Option<String>,
+    /// The most specific platform tag the interpreter supports, e.g.
+    /// "manylinux_2_17_x86_64". Everything it implies is supported too.
+    pub platform: String,
+}
+
+impl InterpreterInfo {
+    pub fn cpython(python_version: (u32, u32), platform: &str) -> InterpreterInfo {
+        let (major, minor) = python_version;
+        InterpreterInfo {
+            implementation: "cp".into(),
+            python_version,
+            abi: Some(format!("cp{major}{minor}")),
+            platform: platform.into(),
+        }
+    }
+}
+
+/// Returns every (python, abi, platform) wheel tag `interpreter` can use, from most
+/// to least preferred, in the same order as 'packaging.tags.sys_tags'. For CPython
+/// 3.11 on manylinux_2_17_x86_64 that's:
+///
+/// - cp311-cp311-{platform}, cp311-abi3-{platform}, cp311-none-{platform}
+/// - cp310-abi3-{platform} ... cp32-abi3-{platform}
+/// - py311-none-{platform}, py3-none-{platform}, py310-none-{platform} ...
+///   py30-none-{platform}
+/// - cp311-none-any, then py311-none-any, py3-none-any, py310-none-any ...
+///   py30-none-any
+///
+/// where each {platform} runs through all the platform tags implied by
+/// `interpreter.platform`, best first.
+///
+/// The result is meant for `WheelName::compatibility`.
+pub fn compatible_tags(interpreter: &InterpreterInfo) -> Vec<(String, String, String)> {
+    let (major, minor) = interpreter.python_version;
+    let platforms = expand_platform_tag(&interpreter.platform);
+    let python = format!("{}{major}{minor}", interpreter.implementation);
+    let is_cpython = interpreter.implementation == "cp";
+
+    let mut tags = Vec::new();
+    let mut push_all = |py: &str, abi: &str, platforms: &[String]| {
+        for platform in platforms {
+            tags.push((py.to_owned(), abi.to_owned(), platform.clone()));
+        }
+    };
+
+    // tags for this exact interpreter
+    let mut abis: Vec<&str> = interpreter.abi.iter().map(|s| s.as_str()).collect();
+    // abi3 is CPython's stable ABI, which appeared in 3.2
+    let has_abi3 = is_cpython && major == 3 && minor >= 2;
+    if has_abi3 {
+        abis.push("abi3");
+    }
+    abis.push("none");
+    for abi in abis {
+        push_all(&python, abi, &platforms);
+    }
+    // abi3 wheels built for older CPythons work on newer ones
+    if has_abi3 {
+        for older in (2..minor).rev() {
+            push_all(&format!("cp{major}{older}"), "abi3", &platforms);
+        }
+    }
+
+    // pure-Python tags: "py311", "py3", "py310", ..., "py30"
+    let mut generic = vec![format!("py{major}{minor}"), format!("py{major}")];
+    generic.extend((0..minor).rev().map(|older| format!("py{major}{older}")));
+    for py in &generic {
+        push_all(py, "none", &platforms);
+    }
+    let any = ["any".to_owned()];
+    push_all(&python, "none", &any);
+    for py in &generic {
+        push_all(py, "none", &any);
+    }
+    tags
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::prelude::*;
+
+    fn tag_strings(tags: &[(String, String, String)]) -> Vec<String> {
+        tags.iter()
+            .map(|(py, abi, platform)| format!("{py}-{abi}-{platform}"))
+            .collect()
+    }
+
+    #[test]
+    fn test_compatible_tags_cpython() {
+        let tags = compatible_tags(&InterpreterInfo::cpython(
+            (3, 11),
+            "manylinux_2_17_x86_64",
+        ));
+        let tags = tag_strings(&tags);
+        assert_eq!(
+            &tags[..4],
+            [
+                "cp311-cp311-manylinux_2_17_x86_64",
+                "cp311-cp311-manylinux2014_x86_64",
+                "cp311-cp311-manylinux_2_16_x86_64",
+                "cp311-cp311-manylinux_2_15_x86_64",
+            ]
+        );
+        let pos = |tag: &str| tags.iter().position(|t| t == tag).unwrap();
+        assert!(
+            pos("cp311-cp311-manylinux_2_5_x86_64")
+                < pos("cp311-abi3-manylinux_2_17_x86_64")
+        );
+        assert!(
+            pos("cp311-abi3-manylinux_2_5_x86_64")
+                < pos("cp311-none-manylinux_2_17_x86_64")
+        );
+        assert!(
+            pos("cp311-none-manylinux_2_5_x86_64")
+                < pos("cp310-abi3-manylinux_2_17_x86_64")
+        );
+        assert!(
+            pos("cp33-abi3-manylinux_2_5_x86_64")
+                < pos("cp32-abi3-manylinux_2_17_x86_64")
+        );
+        assert!(
+            pos("cp32-abi3-manylinux_2_5_x86_64")
+                < pos("py311-none-manylinux_2_17_x86_64")
+        );
+        assert!(
+            pos("py311-none-manylinux_2_5_x86_64")
+                < pos("py3-none-manylinux_2_17_x86_64")
+        );
+        assert!(
+            pos("py3-none-manylinux_2_5_x86_64")
+                < pos("py310-none-manylinux_2_17_x86_64")
+        );
+        assert!(pos("py30-none-manylinux_2_5_x86_64") < pos("cp311-none-any"));
+        assert_eq!(
+            &tags[tags.len() - 14..tags.len() - 10],
+            [
+                "cp311-none-any",
+                "py311-none-any",
+                "py3-none-any",
+                "py310-none-any"
+            ]
+        );
+        assert_eq!(tags.last().unwrap(), "py30-none-any");
+        assert!(!tags.iter().any(|t| t.starts_with("cp31-abi3")));
+        assert!(!tags.iter().any(|t| t.starts_with("cp312")));
+
+        // no duplicates
+        let unique: HashSet<&String> = tags.iter().collect();
+        assert_eq!(unique.len(), tags.len());
+    }
+
+    #[test]
+    fn test_compatible_tags_other_implementations() {
+        let pypy = InterpreterInfo {
+            implementation: "pp".into(),
+            python_version: (3, 9),
+            abi: Some("pypy39_pp73".into()),
+            platform: "win_amd64".into(),
+        };
+        let tags = tag_strings(&compatible_tags(&pypy));
+        assert_eq!(
+            &tags[..4],
+            [
+                "pp39-pypy39_pp73-win_amd64",
+                "pp39-none-win_amd64",
+                "py39-none-win_amd64",
+                "py3-none-win_amd64",
+            ]
+        );
+        // the stable ABI is CPython-only
+        assert!(!tags.iter().any(|t| t.contains("abi3")));
+        assert!(tags.contains(&"pp39-none-any".to_owned()));
+    }
+
+    #[test]
+    fn test_compatible_tags_with_wheel_names() {
+        let tags = compatible_tags(&InterpreterInfo::cpython((3, 11), "win_amd64"));
+        let compat = |name: &str| {
+            let wheel: WheelName = name.try_into().unwrap();
+            wheel.compatibility(&tags)
+        };
+        let native = compat("numpy-1.24.0-cp311-cp311-win_amd64.whl").unwrap();
+        let pure = compat("trio-0.22.0-py3-none-any.whl").unwrap();
+        assert!(native < pure);
+        assert_eq!(compat("numpy-1.24.0-cp310-cp310-win_amd64.whl"), None);
+        assert_eq!(compat("numpy-1.24.0-cp311-cp311-win32.whl"), None);
+    }
+
+    #[test]
+    fn test_abi3_wheels() {
+        let wheel: WheelName =
+            "cryptography-39.0.0-cp36-abi3-manylinux_2_28_x86_64.whl"
+                .try_into()
+                .unwrap();
+        let compat = |interpreter: &InterpreterInfo| {
+            wheel.compatibility(&compatible_tags(interpreter))
+        };
+        let cp311 = InterpreterInfo::cpython((3, 11), "manylinux_2_31_x86_64");
+        let abi3 = compat(&cp311).unwrap();
+        let native: WheelName =
+            "cryptography-39.0.0-cp311-cp311-manylinux_2_28_x86_64.whl"
+                .try_into()
+                .unwrap();
+        let pure: WheelName =
+            "cryptography-39.0.0-py3-none-any.whl".try_into().unwrap();
+        let tags = compatible_tags(&cp311);
+        assert!(native.compatibility(&tags).unwrap() < abi3);
+        assert!(abi3 < pure.compatibility(&tags).unwrap());
+
+        // works on the version it was built for, but not older ones
+        assert!(
+            compat(&InterpreterInfo::cpython((3, 6), "manylinux_2_28_x86_64"))
+                .is_some()
+        );
+        assert!(
+            compat(&InterpreterInfo::cpython((3, 5), "manylinux_2_28_x86_64"))
+                .is_none()
+        );
+        // and it's still limited by the platform tag
+        assert!(
+            compat(&InterpreterInfo::cpython((3, 11), "manylinux_2_17_x86_64"))
+                .is_none()
+        );
+        // non-CPython interpreters don't have the stable ABI
+        assert!(compat(&InterpreterInfo {
+            implementation: "pp".into(),
+            python_version: (3, 9),
+            abi: Some("pypy39_pp73".into()),
+            platform: "manylinux_2_31_x86_64".into(),
+        })
+        .is_none());
+    }
+}
@@ -5,11 +5,54 @@ use pubgrub::range::Range;
 use pubgrub::report::DerivationTree;
 use pubgrub::report::Reporter;
 use pubgrub::solver::{Dependencies, DependencyConstraints};
+use serde_with::{serde_as, DeserializeAs, DisplayFromStr, SerializeAs};
 use std::borrow::Borrow;
 use std::cell::RefCell;
 
 use crate::package_db::{ArtifactInfo, PackageDB};
 
+// Vocab types' own serde impls are behind the optional "serde" feature, so the
+// structs we persist spell out how to (de)serialize them, as their string forms.
+// Mostly that's DisplayFromStr, but PackageName and Extra get this, which writes the
+// normalized name (the same as their own Serialize impls).
+struct NormalizedName;
+
+impl SerializeAs<PackageName> for NormalizedName {
+    fn serialize_as<S>(source: &PackageName, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(source.normalized())
+    }
+}
+
+impl<'de> DeserializeAs<'de, PackageName> for NormalizedName {
+    fn deserialize_as<D>(deserializer: D) -> Result<PackageName, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        DisplayFromStr::deserialize_as(deserializer)
+    }
+}
+
+impl SerializeAs<Extra> for NormalizedName {
+    fn serialize_as<S>(source: &Extra, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(source.normalized())
+    }
+}
+
+impl<'de> DeserializeAs<'de, Extra> for NormalizedName {
+    fn deserialize_as<D>(deserializer: D) -> Result<Extra, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        DisplayFromStr::deserialize_as(deserializer)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(try_from = "AllowPreSerdeHelper", into = "AllowPreSerdeHelper")]
 pub enum AllowPre {
@@ -32,10 +75,11 @@ impl Default for AllowPre {
     }
 }
 
+#[serde_as]
 #[derive(Serialize, Deserialize)]
 #[serde(untagged)]
 enum AllowPreSerdeHelper<'a> {
-    Some(HashSet<PackageName>),
+    Some(#[serde_as(as = "HashSet<NormalizedName>")] HashSet<PackageName>),
     Other(&'a str),
 }
 
@@ -77,10 +121,13 @@ fn allow_pre_is_empty(value: &AllowPre) -> bool {
 /// build. Doesn't necessarily have to be what the user types in exactly, but has to
 /// represent their intentions, and you have to be able to build the whole structure
 /// without looking at a package index.
+#[serde_as]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Brief {
+    #[serde_as(as = "DisplayFromStr")]
     pub python: PythonRequirement,
     // don't need python_constraints because we always install exactly one python
+    #[serde_as(as = "Vec<DisplayFromStr>")]
     pub requirements: Vec<UserRequirement>,
     #[serde(skip_serializing_if = "allow_pre_is_empty")]
     pub allow_pre: AllowPre,
@@ -88,10 +135,14 @@ pub struct Brief {
     //pub constraints: Vec<UserRequirement>,
 }
 
+#[serde_as]
 #[derive(Debug, Clone, Hash, Eq, PartialEq, Serialize, Deserialize)]
 pub struct PinnedPackage {
+    #[serde_as(as = "NormalizedName")]
     pub name: PackageName,
+    #[serde_as(as = "DisplayFromStr")]
     pub version: Version,
+    #[serde_as(as = "Vec<DisplayFromStr>")]
     pub hashes: Vec<ArtifactHash>,
 }
 
@@ -153,11 +204,15 @@ pub struct WheelResolveMetadata {
     pub inner: WheelResolveMetadataInner,
 }
 
+#[serde_as]
 #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct WheelResolveMetadataInner {
+    #[serde_as(as = "Vec<DisplayFromStr>")]
     pub requires_dist: Vec<PackageRequirement>,
+    #[serde_as(as = "DisplayFromStr")]
     pub requires_python: Specifiers,
+    #[serde_as(as = "HashSet<NormalizedName>")]
     pub extras: HashSet<Extra>,
 }
 
@@ -178,7 +233,10 @@ impl WheelResolveMetadata {
 pub struct Blueprint {
     pub pybi: PinnedPackage,
     pub wheels: Vec<(PinnedPackage, WheelResolveMetadata)>,
-    #[serde(serialize_with = "serialize_marker_exprs")]
+    #[serde(
+        serialize_with = "serialize_marker_exprs",
+        deserialize_with = "deserialize_marker_exprs"
+    )]
     pub marker_expressions: HashMap<StandaloneMarkerExpr, bool>,
 }
 
@@ -197,6 +255,18 @@ where
     s.collect_map(stringized.into_iter())
 }
 
+fn deserialize_marker_exprs<'de, D>(
+    d: D,
+) -> Result<HashMap<StandaloneMarkerExpr, bool>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    HashMap::<String, bool>::deserialize(d)?
+        .into_iter()
+        .map(|(k, v)| Ok((k.parse().map_err(serde::de::Error::custom)?, v)))
+        .collect()
+}
+
 impl Display for Blueprint {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         writeln!(f, "pybi: {}", self.pybi)?;
@@ -243,7 +313,12 @@ fn resolve_pybi<'a, 'b>(
     let name = &brief.python.name;
     let versions = fetch_and_sort_versions(&db, &brief, &name, None, hints)?;
     for version in versions.iter() {
-        if brief.python.specifiers.satisfied_by(&version)? {
+        // pre-releases were already filtered out by fetch_and_sort_versions
+        if brief
+            .python
+            .specifiers
+            .satisfied_by_with_prereleases(&version, true)?
+        {
             let artifact_infos = db.artifacts_for_version(&name, version)?;
             if let Some((ai, platform)) = pick_best_pybi(&artifact_infos, platforms) {
                 return Ok((ai, platform));
@@ -396,8 +471,9 @@ fn fetch_and_sort_versions<'a>(
             if let (Some(python_version), Some(requires_python)) =
                 (python_version, &ai.requires_python)
             {
-                let requires_python: Specifiers = requires_python.parse()?;
-                if !requires_python.satisfied_by(&python_version)? {
+                let requires_python: RequiresPython =
+                    requires_python.as_str().try_into()?;
+                if !requires_python.contains(&python_version)? {
                     continue;
                 }
             }
@@ -583,84 +659,6 @@ fn resolve_wheels(
     }
 }
 
-struct ExtraEnv<'a> {
-    extra: Option<&'a str>,
-}
-
-impl<'a> marker::Env for ExtraEnv<'a> {
-    fn get_marker_var(&self, var: &str) -> Option<&str> {
-        if var == "extra" {
-            self.extra.or(Some(""))
-        } else {
-            None
-        }
-    }
-}
-
-enum Simplified {
-    True,
-    False,
-    Expr(marker::EnvMarkerExpr),
-}
-
-impl Simplified {
-    fn eval(&self, env: &dyn marker::Env) -> Result<bool> {
-        match self {
-            Simplified::True => Ok(true),
-            Simplified::False => Ok(false),
-            Simplified::Expr(expr) => expr.eval(env),
-        }
-    }
-}
-
-fn simplify_out_extra(
-    expr: &marker::EnvMarkerExpr,
-    extra: Option<&str>,
-) -> Result<Simplified> {
-    Ok(match expr {
-        marker::EnvMarkerExpr::And(lhs, rhs) => {
-            let lhs = simplify_out_extra(&lhs, extra)?;
-            let rhs = simplify_out_extra(&rhs, extra)?;
-            match (lhs, rhs) {
-                (Simplified::True, Simplified::True) => Simplified::True,
-                (_, Simplified::False) => Simplified::False,
-                (Simplified::False, _) => Simplified::False,
-                (Simplified::Expr(lhs), Simplified::True) => Simplified::Expr(lhs),
-                (Simplified::True, Simplified::Expr(rhs)) => Simplified::Expr(rhs),
-                (Simplified::Expr(lhs), Simplified::Expr(rhs)) => Simplified::Expr(
-                    marker::EnvMarkerExpr::And(Box::new(lhs), Box::new(rhs)),
-                ),
-            }
-        }
-        marker::EnvMarkerExpr::Or(lhs, rhs) => {
-            let lhs = simplify_out_extra(&lhs, extra)?;
-            let rhs = simplify_out_extra(&rhs, extra)?;
-            match (lhs, rhs) {
-                (Simplified::False, Simplified::False) => Simplified::False,
-                (_, Simplified::True) => Simplified::True,
-                (Simplified::True, _) => Simplified::True,
-                (Simplified::Expr(lhs), Simplified::False) => Simplified::Expr(lhs),
-                (Simplified::False, Simplified::Expr(rhs)) => Simplified::Expr(rhs),
-                (Simplified::Expr(lhs), Simplified::Expr(rhs)) => Simplified::Expr(
-                    marker::EnvMarkerExpr::Or(Box::new(lhs), Box::new(rhs)),
-                ),
-            }
-        }
-        marker::EnvMarkerExpr::Operator { op: _, lhs, rhs } => {
-            match expr.eval(&ExtraEnv { extra }) {
-                Ok(true) => Simplified::True,
-                Ok(false) => Simplified::False,
-                Err(_) => {
-                    if rhs.is_extra() || lhs.is_extra() {
-                        bail!("anomalous 'extra' expression: {}", expr);
-                    }
-                    Simplified::Expr(expr.clone())
-                }
-            }
-        }
-    })
-}
-
 // A "package" for purposes of resolving. This is an extended version of what PyPI
 // considers a package, in two ways.
 //
@@ -721,9 +719,9 @@ impl<'a> PubgrubState<'a> {
         for req in reqs {
             if let Some(expr) = &req.env_marker_expr {
                 let simplified =
-                    simplify_out_extra(expr, extra.map(|e| e.normalized()))?;
+                    marker::simplify_out_extra(expr, extra.map(|e| e.normalized()))?;
                 let value = simplified.eval(self.env)?;
-                if let Simplified::Expr(expr) = simplified {
+                if let marker::Simplified::Expr(expr) = simplified {
                     self.marker_exprs
                         .borrow_mut()
                         .insert(StandaloneMarkerExpr(expr), value);
@@ -733,6 +731,10 @@ impl<'a> PubgrubState<'a> {
                 }
             }
 
+            if req.url.is_some() {
+                bail!("direct URL references aren't supported yet: {}", **req);
+            }
+
             let mut maybe_extras: Vec<Option<Extra>> =
                 req.extras.iter().map(|e| Some(e.clone())).collect();
             if maybe_extras.is_empty() {
@@ -752,7 +754,7 @@ impl<'a> PubgrubState<'a> {
 
 fn specifiers_to_pubgrub(specs: &Specifiers) -> Result<Range<Version>> {
     let mut final_range = Range::any();
-    for spec in &specs.0 {
+    for spec in specs {
         let spec_range =
             spec.to_ranges()?
                 .into_iter()
@@ -800,10 +802,10 @@ impl<'a> pubgrub::solver::DependencyProvider<ResPkg, Version> for PubgrubState<'
                     }
 
                     let metadata = self.metadata(&(name.clone(), version.clone()))?;
-                    if !metadata
-                        .requires_python
-                        .satisfied_by(&self.python_full_version)?
-                    {
+                    if !metadata.requires_python.satisfied_by_with_prereleases(
+                        &self.python_full_version,
+                        true,
+                    )? {
                         Err(eyre!(
                             "{} {}: bad requires-python, but pypi didn't tell us!",
                             name.as_given(),
@@ -880,16 +882,7 @@ impl<'a> pubgrub::solver::DependencyProvider<ResPkg, Version> for PubgrubState<'
 #[cfg(test)]
 mod test {
     use super::*;
-
-    impl Display for Simplified {
-        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-            match self {
-                Simplified::True => write!(f, "true"),
-                Simplified::False => write!(f, "false"),
-                Simplified::Expr(e) => write!(f, "{}", e),
-            }
-        }
-    }
+    use marker::simplify_out_extra;
 
     #[test]
     fn test_marker_simplify() {
@@ -0,0 +1,88 @@
+// Random generators for property tests, for our own tests and (with the "arbitrary"
+// feature) anyone else's. They aren't proptest strategies; they just take a
+// fastrand::Rng, so run them in a loop over a few seeds. Everything they produce is
+// accepted by our own parsers, and is in normalized form, so parse(display(x)) == x.
+// They cover epochs, pre/post/dev releases, local versions, and '.*' wildcards.
+
+use crate::prelude::*;
+
+pub fn arbitrary_version(rng: &fastrand::Rng) -> Version {
+    use pep440::{LocalVersion, PreRelease};
+
+    let maybe = |n: u32| {
+        if rng.u8(..4) == 0 {
+            Some(rng.u32(..n))
+        } else {
+            None
+        }
+    };
+    let epoch = if rng.u8(..5) == 0 { rng.u32(1..5) } else { 0 };
+    let release = (0..rng.usize(1..5)).map(|_| rng.u32(..20)).collect();
+    let pre = maybe(10).map(|n| match rng.u8(..3) {
+        0 => PreRelease::A(n),
+        1 => PreRelease::B(n),
+        _ => PreRelease::RC(n),
+    });
+    let local = if rng.u8(..5) == 0 {
+        (0..rng.usize(1..3))
+            .map(|_| {
+                if rng.bool() {
+                    LocalVersion::NumericComponent(rng.u32(..100))
+                } else {
+                    LocalVersion::StringComponent(
+                        (0..rng.usize(1..5)).map(|_| rng.lowercase()).collect(),
+                    )
+                }
+            })
+            .collect()
+    } else {
+        vec![]
+    };
+    Version(pep440::Version {
+        epoch,
+        release,
+        pre,
+        post: maybe(10),
+        dev: maybe(10),
+        local,
+    })
+}
+
+pub fn arbitrary_specifier(rng: &fastrand::Rng) -> Specifier {
+    use CompareOp::*;
+
+    let mut version = arbitrary_version(rng);
+    let op = [
+        LessThanEqual,
+        StrictlyLessThan,
+        NotEqual,
+        Equal,
+        GreaterThanEqual,
+        StrictlyGreaterThan,
+        Compatible,
+    ][rng.usize(..7)];
+    // only == and != allow local versions and wildcards
+    if op != Equal && op != NotEqual {
+        version.0.local.clear();
+    }
+    if op == Compatible && version.0.release.len() < 2 {
+        version.0.release.push(0);
+    }
+    let mut value = version.to_string();
+    if (op == Equal || op == NotEqual)
+        && version.0.dev.is_none()
+        && version.0.local.is_empty()
+        && rng.bool()
+    {
+        value.push_str(".*");
+    }
+    Specifier::new(op, &value).unwrap()
+}
+
+pub fn arbitrary_specifiers(rng: &fastrand::Rng) -> Specifiers {
+    Specifiers(
+        (0..rng.usize(1..4))
+            .map(|_| arbitrary_specifier(rng))
+            .collect(),
+    )
+}
@@ -168,6 +168,38 @@ fn slurp_from_zip<'a, T: Read + Seek>(
     Ok(slurp(&mut z.by_name(name)?)?)
 }
 
+/// The contents of a wheel's .dist-info/WHEEL file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WheelMetadata {
+    pub generator: Option<String>,
+    pub root_is_purelib: bool,
+    pub tags: Vec<String>,
+}
+
+impl TryFrom<&[u8]> for WheelMetadata {
+    type Error = eyre::Report;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        let mut parsed =
+            parse_format_metadata_and_check_version(value, "Wheel-Version")?;
+
+        let root_is_purelib = match &parsed.take_the("Root-Is-Purelib")?[..] {
+            "true" => true,
+            "false" => false,
+            other => bail!(
+                "Expected 'true' or 'false' for Root-Is-Purelib, not {}",
+                other,
+            ),
+        };
+
+        Ok(WheelMetadata {
+            generator: parsed.maybe_take_the("Generator")?,
+            root_is_purelib,
+            tags: parsed.take_all("Tag"),
+        })
+    }
+}
+
 struct WheelVitals {
     dist_info: String,
     data: String,
@@ -276,17 +308,8 @@ impl Wheel {
         let wheel_path = format!("{dist_info}/WHEEL");
         let wheel_metadata = slurp_from_zip(&mut z, &wheel_path)?;
 
-        let mut parsed =
-            parse_format_metadata_and_check_version(&wheel_metadata, "Wheel-Version")?;
-
-        let root_is_purelib = match &parsed.take_the("Root-Is-Purelib")?[..] {
-            "true" => true,
-            "false" => false,
-            other => bail!(
-                "Expected 'true' or 'false' for Root-Is-Purelib, not {}",
-                other,
-            ),
-        };
+        let root_is_purelib =
+            WheelMetadata::try_from(wheel_metadata.as_slice())?.root_is_purelib;
 
         let metadata_path = format!("{dist_info}/METADATA");
         let metadata_blob = slurp_from_zip(&mut z, &metadata_path)?;
@@ -604,3 +627,48 @@ where
         bail!("symlinks not supported in wheels");
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn test_wheel_metadata_parse() {
+        let wheel_file = indoc! {"
+            Wheel-Version: 1.0
+            Generator: bdist_wheel (0.38.4)
+            Root-Is-Purelib: false
+            Tag: cp311-cp311-manylinux_2_17_x86_64
+            Tag: cp311-cp311-manylinux2014_x86_64
+        "};
+        let metadata: WheelMetadata = wheel_file.as_bytes().try_into().unwrap();
+        assert_eq!(
+            metadata,
+            WheelMetadata {
+                generator: Some("bdist_wheel (0.38.4)".into()),
+                root_is_purelib: false,
+                tags: vec![
+                    "cp311-cp311-manylinux_2_17_x86_64".into(),
+                    "cp311-cp311-manylinux2014_x86_64".into(),
+                ],
+            }
+        );
+
+        let minimal = "Wheel-Version: 1.0\nRoot-Is-Purelib: true\n";
+        let metadata: WheelMetadata = minimal.as_bytes().try_into().unwrap();
+        assert!(metadata.root_is_purelib);
+        assert_eq!(metadata.generator, None);
+        assert!(metadata.tags.is_empty());
+
+        let bad_purelib = "Wheel-Version: 1.0\nRoot-Is-Purelib: yes\n";
+        let err = WheelMetadata::try_from(bad_purelib.as_bytes()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Expected 'true' or 'false' for Root-Is-Purelib, not yes"
+        );
+
+        let bad_version = "Wheel-Version: 2.0\nRoot-Is-Purelib: true\n";
+        assert!(WheelMetadata::try_from(bad_version.as_bytes()).is_err());
+    }
+}
@@ -1,6 +1,7 @@
 use crate::prelude::*;
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, SerializeDisplay, DeserializeFromStr)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(SerializeDisplay, DeserializeFromStr))]
 pub struct ArtifactHash {
     pub mode: String,
     pub raw_data: Vec<u8>,
@@ -96,9 +97,12 @@ mod test {
             "sha256=c27c231e66336183c484fbfe080fa6cc954149366c15dc21db8b7290081ec7b8"
         );
 
-        let json = serde_json::to_string(&value).unwrap();
-        let new_value: ArtifactHash = serde_json::from_str(&json).unwrap();
-        assert_eq!(value, new_value);
+        #[cfg(feature = "serde")]
+        {
+            let json = serde_json::to_string(&value).unwrap();
+            let new_value: ArtifactHash = serde_json::from_str(&json).unwrap();
+            assert_eq!(value, new_value);
+        }
     }
 
     #[test]
@@ -17,27 +17,29 @@ impl TryFrom<&str> for SdistName {
     type Error = eyre::Report;
 
     fn try_from(value: &str) -> Result<Self, Self::Error> {
-        static SDIST_NAME_RE: Lazy<Regex> =
-            Lazy::new(|| Regex::new(r"^(.*)-([^-]*)\.(zip|tar\.gz)$").unwrap());
-
-        match SDIST_NAME_RE.captures(&value) {
-            None => bail!("invalid sdist name"),
-            Some(captures) => {
-                let distribution: PackageName =
-                    captures.get(1).unwrap().as_str().parse()?;
-                let version: Version = captures.get(2).unwrap().as_str().parse()?;
-                let format = match captures.get(3).unwrap().as_str() {
-                    "zip" => SdistFormat::Zip,
-                    "tar.gz" => SdistFormat::TarGz,
-                    _ => unreachable!(),
-                };
-                Ok(SdistName {
-                    distribution,
+        let (stem, format) = if let Some(stem) = value.strip_suffix(".tar.gz") {
+            (stem, SdistFormat::TarGz)
+        } else if let Some(stem) = value.strip_suffix(".zip") {
+            (stem, SdistFormat::Zip)
+        } else {
+            bail!("expected {:?} to end in .tar.gz or .zip", value);
+        };
+        // Both names and versions can contain '-' (legacy versions like "1.0-1" are
+        // still valid PEP 440), so the filename alone is ambiguous. Names are much
+        // more likely to have several '-'-separated words than versions are, so we
+        // use the first split where the rest is a valid version: "foo-bar-1.0" is
+        // foo-bar 1.0, and "foo-1.0-1" is foo 1.0.post1.
+        for (i, _) in stem.match_indices('-') {
+            let (name, version) = (&stem[..i], &stem[i + 1..]);
+            if let Ok(version) = Version::try_from(version) {
+                return Ok(SdistName {
+                    distribution: name.try_into()?,
                     version,
                     format,
-                })
+                });
             }
         }
+        bail!("can't find a name and version in sdist name {:?}", value)
     }
 }
 
@@ -58,8 +60,78 @@ impl Display for SdistName {
     }
 }
 
-// https://packaging.python.org/specifications/binary-distribution-format/#file-name-convention
+// Legacy setuptools eggs: {name}-{version}[-py{X.Y}[-{platform}]].egg, with any '-' in
+// the name or version escaped as '_', so '-' only separates fields -- except in the
+// platform, which comes from distutils.util.get_platform() and can have '-' in it
+// ("linux-x86_64"). We can't install these; this is just so we can recognize them.
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub struct EggName {
+    pub distribution: PackageName,
+    pub version: Version,
+    /// e.g. "3.8" for "py3.8"
+    pub python: Option<String>,
+    pub platform: Option<String>,
+}
+
+impl TryFrom<&str> for EggName {
+    type Error = eyre::Report;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let stem = value
+            .strip_suffix(".egg")
+            .ok_or_else(|| eyre!("expected {:?} to end in .egg", value))?;
+        let mut pieces = stem.splitn(4, '-');
+        let (Some(name), Some(version)) = (pieces.next(), pieces.next()) else {
+            bail!("can't parse egg name {:?}: missing version", value);
+        };
+        let python = match pieces.next() {
+            None => None,
+            Some(tag) => Some(
+                tag.strip_prefix("py")
+                    .filter(|v| !v.is_empty())
+                    .ok_or_else(|| {
+                        eyre!("invalid python tag {:?} in egg name {:?}", tag, value)
+                    })?
+                    .to_owned(),
+            ),
+        };
+        Ok(EggName {
+            distribution: name.try_into()?,
+            version: version.try_into()?,
+            python,
+            platform: pieces.next().map(|p| p.to_owned()),
+        })
+    }
+}
+
+try_from_str_boilerplate!(EggName);
+
+impl Display for EggName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}-{}",
+            self.distribution.wheel_distribution_name(),
+            self.version
+        )?;
+        if let Some(python) = &self.python {
+            write!(f, "-py{}", python)?;
+        }
+        if let Some(platform) = &self.platform {
+            write!(f, "-{}", platform)?;
+        }
+        write!(f, ".egg")
+    }
+}
+
+// https://packaging.python.org/specifications/binary-distribution-format/#file-name-convention
+//
+// Local versions appear in filenames either with their '+', like
+// "torch-2.0.0+cu118-...", or escaped as "torch-2.0.0_cu118-...". Both parse to the
+// same version and compare equal, but we remember which spelling we saw and Display
+// writes it back out the same way (like PackageName does with its as_given spelling).
+#[derive(Clone, Debug, Derivative)]
+#[derivative(PartialEq, Eq, PartialOrd, Ord)]
 pub struct WheelName {
     pub distribution: PackageName,
     pub version: Version,
@@ -84,33 +156,100 @@ pub struct WheelName {
     pub py_tags: Vec<String>,
     pub abi_tags: Vec<String>,
     pub arch_tags: Vec<String>,
+    #[derivative(PartialEq = "ignore", PartialOrd = "ignore", Ord = "ignore")]
+    local_escaped: bool,
 }
 
 pub trait BinaryName {
     fn all_tags(&self) -> HashSet<String>;
 }
 
-impl BinaryName for WheelName {
-    fn all_tags(&self) -> HashSet<String> {
-        let mut retval = HashSet::new();
+impl WheelName {
+    pub fn distribution(&self) -> &PackageName {
+        &self.distribution
+    }
+
+    pub fn version(&self) -> &Version {
+        &self.version
+    }
+
+    /// The python tags, e.g. `["py2", "py3"]` for `py2.py3-none-any`, in the order
+    /// they appear in the filename.
+    pub fn python_tags(&self) -> &[String] {
+        &self.py_tags
+    }
+
+    pub fn abi_tags(&self) -> &[String] {
+        &self.abi_tags
+    }
+
+    /// The platform tags, e.g. `["manylinux_2_17_x86_64", "manylinux2014_x86_64"]`.
+    pub fn platform_tags(&self) -> &[String] {
+        &self.arch_tags
+    }
+
+    /// Expands the compressed tag sets into individual (python, abi, platform)
+    /// triples, e.g. `py2.py3-none-any` gives `(py2, none, any)` and
+    /// `(py3, none, any)`.
+    pub fn tags(&self) -> Vec<(String, String, String)> {
+        let mut retval = Vec::new();
         for py in &self.py_tags {
             for abi in &self.abi_tags {
                 for arch in &self.arch_tags {
-                    retval.insert(format!("{}-{}-{}", py, abi, arch));
+                    retval.push((py.clone(), abi.clone(), arch.clone()));
                 }
             }
         }
         retval
     }
+
+    /// The build tag, split into its leading number and the remainder, e.g. `10abc`
+    /// gives `(10, "abc")`. None if there's no build tag (or it doesn't start with a
+    /// digit, which the spec doesn't allow).
+    pub fn build_tag(&self) -> Option<(u32, String)> {
+        self.build_number.map(|num| (num, self.build_name.clone()))
+    }
+
+    /// Orders two wheels by build tag alone, so that when several wheels differ only
+    /// in their build tag the highest one can win. Wheels with no build tag sort
+    /// below all wheels that have one.
+    pub fn cmp_build_tag(&self, other: &WheelName) -> std::cmp::Ordering {
+        (self.build_number, &self.build_name)
+            .cmp(&(other.build_number, &other.build_name))
+    }
+
+    /// Given an interpreter's supported tags, ordered from most to least preferred,
+    /// returns the index of the most preferred one that this wheel matches, or None
+    /// if the wheel can't be installed. Lower is better.
+    pub fn compatibility(
+        &self,
+        supported: &[(String, String, String)],
+    ) -> Option<usize> {
+        let tags: HashSet<(String, String, String)> = self.tags().into_iter().collect();
+        supported.iter().position(|tag| tags.contains(tag))
+    }
 }
 
-#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+impl BinaryName for WheelName {
+    fn all_tags(&self) -> HashSet<String> {
+        self.tags()
+            .into_iter()
+            .map(|(py, abi, arch)| format!("{}-{}-{}", py, abi, arch))
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug, Derivative)]
+#[derivative(PartialEq, Eq, PartialOrd, Ord)]
 pub struct PybiName {
     pub distribution: PackageName,
     pub version: Version,
     pub build_number: Option<u32>,
     pub build_name: String,
     pub arch_tags: Vec<String>,
+    // see WheelName
+    #[derivative(PartialEq = "ignore", PartialOrd = "ignore", Ord = "ignore")]
+    local_escaped: bool,
 }
 
 impl BinaryName for PybiName {
@@ -123,7 +262,13 @@ fn generic_parse<'a>(
     value: &'a str,
     suffix: &str,
     tag_parts: u8,
-) -> Result<(PackageName, Version, Option<u32>, String, Vec<Vec<String>>)> {
+) -> Result<(
+    PackageName,
+    (Version, bool),
+    Option<u32>,
+    String,
+    Vec<Vec<String>>,
+)> {
     static VALID_CHARS: Lazy<Regex> =
         Lazy::new(|| Regex::new(r"^[A-Za-z0-9_.+!-]*$").unwrap());
 
@@ -138,7 +283,7 @@ fn generic_parse<'a>(
     }
     let stem = value
         .strip_suffix(suffix)
-        .ok_or_else(|| eyre!("expected {:?} to end in .{}", value, suffix))?;
+        .ok_or_else(|| eyre!("expected {:?} to end in {}", value, suffix))?;
 
     let mut pieces: Vec<&str> = stem.split('-').collect();
 
@@ -164,19 +309,49 @@ fn generic_parse<'a>(
     }
 
     if pieces.len() != 2 + tag_parts as usize {
-        bail!("can't parse binary name '{value}'");
+        bail!(
+            "can't parse binary name {:?}: expected {} or {} '-'-separated fields",
+            value,
+            2 + tag_parts,
+            3 + tag_parts,
+        );
     }
 
     let distribution: PackageName = pieces[0].try_into()?;
-    let version: Version = pieces[1].try_into()?;
+    let version = binary_name_version(pieces[1])?;
     let tag_sets: Vec<Vec<String>> = pieces[2..]
         .into_iter()
         .map(|compressed_tag| compressed_tag.split(".").map(|tag| tag.into()).collect())
         .collect();
+    if tag_sets.iter().flatten().any(|tag: &String| tag.is_empty()) {
+        bail!("found empty tag in binary name {:?}", value);
+    }
 
     Ok((distribution, version, build_number, build_name, tag_sets))
 }
 
+// Some tools escape the '+' of a local version as '_', like "1.0_cu118" for
+// "1.0+cu118", since they treat every non-alphanumeric character in a filename
+// component the same way. '_' is also a legal separator inside a version ("1.0_post1"),
+// so we only try this if the version doesn't parse as written. Then we try each '_'
+// from the right, so "1.0_post1_cu118" gives "1.0.post1+cu118". Returns whether the
+// '+' was escaped, so Display can escape it again.
+fn binary_name_version(value: &str) -> Result<(Version, bool)> {
+    let err = match Version::try_from(value) {
+        Ok(version) => return Ok((version, false)),
+        Err(err) => err,
+    };
+    if !value.contains('+') {
+        for (i, _) in value.rmatch_indices('_') {
+            let unescaped = format!("{}+{}", &value[..i], &value[i + 1..]);
+            if let Ok(version) = Version::try_from(unescaped.as_str()) {
+                return Ok((version, true));
+            }
+        }
+    }
+    Err(err)
+}
+
 fn format_build_tag(build_number: Option<u32>, build_name: &str) -> String {
     match (build_number, &build_name[..]) {
         (None, "") => String::from(""),
@@ -185,12 +360,26 @@ fn format_build_tag(build_number: Option<u32>, build_name: &str) -> String {
     }
 }
 
+fn format_binary_name_version(version: &Version, local_escaped: bool) -> String {
+    let version = version.to_string();
+    if local_escaped {
+        version.replacen('+', "_", 1)
+    } else {
+        version
+    }
+}
+
 impl TryFrom<&str> for WheelName {
     type Error = eyre::Report;
 
     fn try_from(value: &str) -> Result<Self, Self::Error> {
-        let (distribution, version, build_number, build_name, mut tag_sets) =
-            generic_parse(value, ".whl", 3)?;
+        let (
+            distribution,
+            (version, local_escaped),
+            build_number,
+            build_name,
+            mut tag_sets,
+        ) = generic_parse(value, ".whl", 3)?;
 
         tag_sets.reverse();
 
@@ -202,6 +391,7 @@ impl TryFrom<&str> for WheelName {
             py_tags: tag_sets.pop().unwrap(),
             abi_tags: tag_sets.pop().unwrap(),
             arch_tags: tag_sets.pop().unwrap(),
+            local_escaped,
         })
     }
 }
@@ -212,8 +402,13 @@ impl TryFrom<&str> for PybiName {
     type Error = eyre::Report;
 
     fn try_from(value: &str) -> Result<Self, Self::Error> {
-        let (distribution, version, build_number, build_name, mut tag_sets) =
-            generic_parse(value, ".pybi", 1)?;
+        let (
+            distribution,
+            (version, local_escaped),
+            build_number,
+            build_name,
+            mut tag_sets,
+        ) = generic_parse(value, ".pybi", 1)?;
 
         Ok(PybiName {
             distribution,
@@ -221,6 +416,7 @@ impl TryFrom<&str> for PybiName {
             build_number,
             build_name,
             arch_tags: tag_sets.pop().unwrap(),
+            local_escaped,
         })
     }
 }
@@ -232,8 +428,8 @@ impl Display for WheelName {
         write!(
             f,
             "{dist}-{ver}{build}-{py_tags}-{abi_tags}-{arch_tags}.whl",
-            dist = self.distribution.as_given(),
-            ver = self.version,
+            dist = self.distribution.wheel_distribution_name(),
+            ver = format_binary_name_version(&self.version, self.local_escaped),
             build = format_build_tag(self.build_number, &self.build_name),
             py_tags = self.py_tags.join("."),
             abi_tags = self.abi_tags.join("."),
@@ -247,27 +443,47 @@ impl Display for PybiName {
         write!(
             f,
             "{dist}-{ver}{build}-{arch_tags}.pybi",
-            dist = self.distribution.as_given(),
-            ver = self.version,
+            dist = self.distribution.wheel_distribution_name(),
+            ver = format_binary_name_version(&self.version, self.local_escaped),
             build = format_build_tag(self.build_number, &self.build_name),
             arch_tags = self.arch_tags.join("."),
         )
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, SerializeDisplay)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(SerializeDisplay))]
 pub enum ArtifactName {
     Sdist(SdistName),
     Wheel(WheelName),
     Pybi(PybiName),
+    Egg(EggName),
 }
 
 impl ArtifactName {
+    /// Figures out what kind of artifact a filename refers to from its extension,
+    /// and parses it accordingly. Fails for anything that isn't a wheel, pybi, sdist
+    /// (.tar.gz or .zip), or egg, or if the name doesn't parse as that kind.
+    pub fn parse(filename: &str) -> Result<ArtifactName> {
+        Ok(if filename.ends_with(".whl") {
+            ArtifactName::Wheel(filename.try_into()?)
+        } else if filename.ends_with(".pybi") {
+            ArtifactName::Pybi(filename.try_into()?)
+        } else if filename.ends_with(".egg") {
+            ArtifactName::Egg(filename.try_into()?)
+        } else if filename.ends_with(".tar.gz") || filename.ends_with(".zip") {
+            ArtifactName::Sdist(filename.try_into()?)
+        } else {
+            bail!("unrecognized artifact type: {:?}", filename);
+        })
+    }
+
     pub fn distribution(&self) -> &PackageName {
         match self {
             ArtifactName::Sdist(inner) => &inner.distribution,
             ArtifactName::Wheel(inner) => &inner.distribution,
             ArtifactName::Pybi(inner) => &inner.distribution,
+            ArtifactName::Egg(inner) => &inner.distribution,
         }
     }
 
@@ -276,6 +492,7 @@ impl ArtifactName {
             ArtifactName::Sdist(inner) => &inner.version,
             ArtifactName::Wheel(inner) => &inner.version,
             ArtifactName::Pybi(inner) => &inner.version,
+            ArtifactName::Egg(inner) => &inner.version,
         }
     }
 
@@ -320,6 +537,12 @@ impl ArtifactName {
     }
 }
 
+/// Just the version from an artifact filename of any kind, e.g. for sorting a
+/// directory listing. Same rules as `ArtifactName::parse`.
+pub fn artifact_version(filename: &str) -> Result<Version> {
+    Ok(ArtifactName::parse(filename)?.version().clone())
+}
+
 pub trait UnwrapFromArtifactName {
     fn try_unwrap_from(value: &ArtifactName) -> Option<&Self>;
 }
@@ -341,18 +564,13 @@ macro_rules! impl_unwrap {
 impl_unwrap!(Sdist, SdistName);
 impl_unwrap!(Wheel, WheelName);
 impl_unwrap!(Pybi, PybiName);
+impl_unwrap!(Egg, EggName);
 
 impl TryFrom<&str> for ArtifactName {
     type Error = eyre::Report;
 
     fn try_from(value: &str) -> Result<Self, Self::Error> {
-        if value.ends_with(".whl") {
-            Ok(ArtifactName::Wheel(value.try_into()?))
-        } else if value.ends_with(".pybi") {
-            Ok(ArtifactName::Pybi(value.try_into()?))
-        } else {
-            Ok(ArtifactName::Sdist(value.try_into()?))
-        }
+        ArtifactName::parse(value)
     }
 }
 
@@ -362,6 +580,7 @@ impl Display for ArtifactName {
             ArtifactName::Sdist(inner) => write!(f, "{}", &inner),
             ArtifactName::Wheel(inner) => write!(f, "{}", &inner),
             ArtifactName::Pybi(inner) => write!(f, "{}", &inner),
+            ArtifactName::Egg(inner) => write!(f, "{}", &inner),
         }
     }
 }
@@ -375,6 +594,142 @@ mod test {
         let sn: SdistName = "trio-0.19a0.tar.gz".try_into().unwrap();
         assert_eq!(sn.distribution, "trio".try_into().unwrap());
         assert_eq!(sn.version, "0.19a0".try_into().unwrap());
+        assert_eq!(sn.format, SdistFormat::TarGz);
+
+        let sn: SdistName = "foo-bar-1.2.3.tar.gz".try_into().unwrap();
+        assert_eq!(sn.distribution.as_given(), "foo-bar");
+        assert_eq!(sn.version, "1.2.3".try_into().unwrap());
+
+        let sn: SdistName = "foo-1.0.zip".try_into().unwrap();
+        assert_eq!(sn.distribution.as_given(), "foo");
+        assert_eq!(sn.version, "1.0".try_into().unwrap());
+        assert_eq!(sn.format, SdistFormat::Zip);
+        assert_eq!(sn.to_string(), "foo-1.0.zip");
+
+        // a '-' inside the version
+        let sn: SdistName = "foo-bar-1.0-1.tar.gz".try_into().unwrap();
+        assert_eq!(sn.distribution.as_given(), "foo-bar");
+        assert_eq!(sn.version.to_string(), "1.0.post1");
+
+        for bad in ["foo-1.0.tar.bz2", "foo.tar.gz", "foo-bar.zip", "-1.0.zip"] {
+            assert!(SdistName::try_from(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_egg_name_from_str() {
+        let egg: EggName = "foo-1.0.egg".try_into().unwrap();
+        assert_eq!(egg.distribution.as_given(), "foo");
+        assert_eq!(egg.version, "1.0".try_into().unwrap());
+        assert_eq!(egg.python, None);
+        assert_eq!(egg.platform, None);
+        assert_eq!(egg.to_string(), "foo-1.0.egg");
+
+        let egg: EggName = "foo_bar-1.0-py3.8.egg".try_into().unwrap();
+        assert_eq!(egg.distribution, "foo-bar".try_into().unwrap());
+        assert_eq!(egg.python.as_deref(), Some("3.8"));
+        assert_eq!(egg.platform, None);
+        assert_eq!(egg.to_string(), "foo_bar-1.0-py3.8.egg");
+
+        let egg: EggName = "lxml-2.3-py2.7-linux-x86_64.egg".try_into().unwrap();
+        assert_eq!(egg.python.as_deref(), Some("2.7"));
+        assert_eq!(egg.platform.as_deref(), Some("linux-x86_64"));
+        assert_eq!(egg.to_string(), "lxml-2.3-py2.7-linux-x86_64.egg");
+
+        for bad in [
+            "foo-1.0.whl",
+            "foo.egg",
+            "foo-1.0-cp38.egg",
+            "foo-1.0-py.egg",
+        ] {
+            assert!(EggName::try_from(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_artifact_name_parse() {
+        let cases = [
+            "trio-0.22.0-py3-none-any.whl",
+            "trio-0.22.0.tar.gz",
+            "trio-0.22.0.zip",
+            "trio-0.22.0-py3.8.egg",
+            "cpython-3.11.1-manylinux_2_17_x86_64.pybi",
+        ];
+        for filename in cases {
+            let name = ArtifactName::parse(filename).unwrap();
+            assert_eq!(
+                name.distribution().normalized(),
+                match filename {
+                    f if f.starts_with("cpython") => "cpython",
+                    _ => "trio",
+                }
+            );
+            assert_eq!(name.to_string(), filename);
+        }
+        assert!(ArtifactName::parse(cases[0])
+            .unwrap()
+            .inner_as::<WheelName>()
+            .is_some());
+        assert!(matches!(
+            ArtifactName::parse(cases[1]).unwrap(),
+            ArtifactName::Sdist(SdistName {
+                format: SdistFormat::TarGz,
+                ..
+            })
+        ));
+        let egg = ArtifactName::parse(cases[3]).unwrap();
+        assert_eq!(
+            egg.inner_as::<EggName>().unwrap().python.as_deref(),
+            Some("3.8")
+        );
+        assert!(egg.inner_as::<SdistName>().is_none());
+        assert!(matches!(
+            ArtifactName::parse(cases[4]).unwrap(),
+            ArtifactName::Pybi(_)
+        ));
+
+        let err = ArtifactName::parse("trio-0.22.0.tar.bz2").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            r#"unrecognized artifact type: "trio-0.22.0.tar.bz2""#
+        );
+        assert!(ArtifactName::parse("trio-0.22.0.exe").is_err());
+        // right extension, bad name
+        assert!(ArtifactName::parse("trio.whl").is_err());
+    }
+
+    #[test]
+    fn test_artifact_version() {
+        let mut filenames = vec![
+            "foo-2.0-py3-none-any.whl",
+            "foo-1.10.tar.gz",
+            "foo-1.9-py2.7.egg",
+            "foo-1.10-py3-none-any.whl",
+            "foo-2.0rc1.zip",
+            "foo-1.9.zip",
+        ];
+        filenames.sort_by_key(|f| artifact_version(f).unwrap());
+        assert_eq!(
+            filenames,
+            [
+                "foo-1.9-py2.7.egg",
+                "foo-1.9.zip",
+                "foo-1.10.tar.gz",
+                "foo-1.10-py3-none-any.whl",
+                "foo-2.0rc1.zip",
+                "foo-2.0-py3-none-any.whl",
+            ]
+        );
+        // same version, however it's spelled in the filename
+        assert_eq!(
+            artifact_version("foo-1.0.post1.tar.gz").unwrap(),
+            artifact_version("foo-1.0_post1-py3.8-linux-x86_64.egg").unwrap()
+        );
+        assert_eq!(
+            artifact_version("foo-1.0.post1.tar.gz").unwrap(),
+            artifact_version("foo-1.0.post1-cp311-cp311-win_amd64.whl").unwrap()
+        );
+        assert!(artifact_version("foo-1.0.msi").is_err());
     }
 
     #[test]
@@ -412,7 +767,208 @@ mod test {
                 .collect()
         );
 
-        assert_eq!(n.to_string(), "foo.bar-0.1b3-1local-py2.py3-none-any.whl");
+        // the distribution name gets escaped on the way out
+        assert_eq!(n.to_string(), "foo_bar-0.1b3-1local-py2.py3-none-any.whl");
+    }
+
+    #[test]
+    fn test_wheel_name_accessors() {
+        let n: WheelName =
+            "Foo_Bar-2.0-cp38.cp39-abi3-manylinux_2_17_x86_64.manylinux2014_x86_64.whl"
+                .try_into()
+                .unwrap();
+        assert_eq!(n.distribution(), &"foo-bar".try_into().unwrap());
+        assert_eq!(n.version(), &"2.0".try_into().unwrap());
+        assert_eq!(n.python_tags(), ["cp38", "cp39"]);
+        assert_eq!(n.abi_tags(), ["abi3"]);
+        assert_eq!(
+            n.platform_tags(),
+            ["manylinux_2_17_x86_64", "manylinux2014_x86_64"]
+        );
+    }
+
+    #[test]
+    fn test_wheel_name_display_roundtrip() {
+        for name in [
+            "trio-0.18.0-py3-none-any.whl",
+            "numpy-1.24.0-cp311-cp311-manylinux_2_17_x86_64.manylinux2014_x86_64.whl",
+            "foo_bar-1.0.post1-7-py2.py3-none-any.whl",
+            "Foo_Bar-1!2.0rc1-cp38-abi3-win_amd64.whl",
+        ] {
+            let n: WheelName = name.try_into().unwrap();
+            assert_eq!(n.to_string(), name);
+        }
+
+        let mut n: WheelName = "trio-0.18.0-py3-none-any.whl".try_into().unwrap();
+        n.build_number = Some(2);
+        n.build_name = "custom".into();
+        assert_eq!(n.to_string(), "trio-0.18.0-2custom-py3-none-any.whl");
+        let reparsed: WheelName = n.to_string().try_into().unwrap();
+        assert_eq!(reparsed, n);
+    }
+
+    #[test]
+    fn test_wheel_name_local_version() {
+        let name = "torch-2.0.0+cu118-cp311-cp311-linux_x86_64.whl";
+        let n: WheelName = name.try_into().unwrap();
+        assert_eq!(n.version, "2.0.0+cu118".try_into().unwrap());
+        assert_eq!(n.to_string(), name);
+
+        let escaped_name = "torch-2.0.0_cu118-cp311-cp311-linux_x86_64.whl";
+        let escaped: WheelName = escaped_name.try_into().unwrap();
+        assert_eq!(escaped, n);
+        assert_eq!(escaped.to_string(), escaped_name);
+
+        let escaped: WheelName = "foo-1.0_post1_ubuntu_7-py3-none-any.whl"
+            .try_into()
+            .unwrap();
+        assert_eq!(escaped.version.to_string(), "1.0.post1+ubuntu.7");
+        // the version is still normalized, but the local separator stays escaped
+        assert_eq!(
+            escaped.to_string(),
+            "foo-1.0.post1_ubuntu.7-py3-none-any.whl"
+        );
+        let reparsed: WheelName = escaped.to_string().try_into().unwrap();
+        assert_eq!(reparsed, escaped);
+
+        let pybi_name = "cpython-3.11.0_local-manylinux_2_17_x86_64.pybi";
+        let pybi: PybiName = pybi_name.try_into().unwrap();
+        assert_eq!(pybi.version, "3.11.0+local".try_into().unwrap());
+        assert_eq!(pybi.to_string(), pybi_name);
+
+        // '_' that's just a separator is left alone
+        let n: WheelName = "foo-1.0_post1-py3-none-any.whl".try_into().unwrap();
+        assert_eq!(n.version.to_string(), "1.0.post1");
+        assert!(WheelName::try_from("foo-1.0_-py3-none-any.whl").is_err());
+    }
+
+    #[test]
+    fn test_wheel_name_tags() {
+        let n: WheelName = "pkg-1.0-py2.py3-none-any.whl".try_into().unwrap();
+        assert_eq!(
+            n.tags(),
+            vec![
+                ("py2".to_owned(), "none".to_owned(), "any".to_owned()),
+                ("py3".to_owned(), "none".to_owned(), "any".to_owned()),
+            ]
+        );
+
+        let n: WheelName =
+            "pkg-1.0-cp38.cp39-abi3-manylinux1_x86_64.manylinux2010_x86_64.whl"
+                .try_into()
+                .unwrap();
+        assert_eq!(n.tags().len(), 4);
+        assert_eq!(n.tags().len(), n.all_tags().len());
+    }
+
+    #[test]
+    fn test_wheel_name_build_tag() {
+        use std::cmp::Ordering;
+
+        let wheel = |name: &str| -> WheelName { name.try_into().unwrap() };
+        let none = wheel("foo-1.0-py3-none-any.whl");
+        let one = wheel("foo-1.0-1-py3-none-any.whl");
+        let two = wheel("foo-1.0-2-py3-none-any.whl");
+        let ten_abc = wheel("foo-1.0-10abc-py3-none-any.whl");
+
+        assert_eq!(none.build_tag(), None);
+        assert_eq!(one.build_tag(), Some((1, "".into())));
+        assert_eq!(ten_abc.build_tag(), Some((10, "abc".into())));
+
+        assert_eq!(none.cmp_build_tag(&one), Ordering::Less);
+        assert_eq!(one.cmp_build_tag(&two), Ordering::Less);
+        // numeric, not lexicographic
+        assert_eq!(ten_abc.cmp_build_tag(&two), Ordering::Greater);
+        assert_eq!(one.cmp_build_tag(&one.clone()), Ordering::Equal);
+
+        let best = [&one, &ten_abc, &none, &two]
+            .into_iter()
+            .max_by(|a, b| a.cmp_build_tag(b))
+            .unwrap();
+        assert_eq!(best, &ten_abc);
+    }
+
+    #[test]
+    fn test_wheel_name_compatibility() {
+        let supported: Vec<(String, String, String)> = [
+            ("cp311", "cp311", "manylinux_2_17_x86_64"),
+            ("cp311", "abi3", "manylinux_2_17_x86_64"),
+            ("cp311", "none", "manylinux_2_17_x86_64"),
+            ("cp311", "none", "any"),
+            ("py3", "none", "any"),
+        ]
+        .into_iter()
+        .map(|(py, abi, arch)| (py.into(), abi.into(), arch.into()))
+        .collect();
+
+        let compat = |name: &str| {
+            let n: WheelName = name.try_into().unwrap();
+            n.compatibility(&supported)
+        };
+        assert_eq!(
+            compat("numpy-1.24.0-cp311-cp311-manylinux_2_17_x86_64.whl"),
+            Some(0)
+        );
+        assert_eq!(
+            compat("cryptography-39.0-cp311-abi3-manylinux_2_17_x86_64.whl"),
+            Some(1)
+        );
+        assert_eq!(compat("trio-0.22.0-py3-none-any.whl"), Some(4));
+        // picks the best of several tags
+        assert_eq!(compat("six-1.16.0-py2.py3.cp311-none-any.whl"), Some(3));
+        assert_eq!(
+            compat("numpy-1.24.0-cp310-cp310-manylinux_2_17_x86_64.whl"),
+            None
+        );
+        assert_eq!(compat("numpy-1.24.0-cp311-cp311-win_amd64.whl"), None);
+    }
+
+    #[test]
+    fn test_wheel_name_numpy() {
+        let n: WheelName = "numpy-1.24.0-cp311-cp311-manylinux_2_17_x86_64.whl"
+            .try_into()
+            .unwrap();
+        assert_eq!(n.distribution, "numpy".try_into().unwrap());
+        assert_eq!(n.version, "1.24.0".try_into().unwrap());
+        assert_eq!(n.build_number, None);
+        assert_eq!(n.build_name, "");
+        assert_eq!(n.py_tags, vec!["cp311"]);
+        assert_eq!(n.abi_tags, vec!["cp311"]);
+        assert_eq!(n.arch_tags, vec!["manylinux_2_17_x86_64"]);
+
+        let n: WheelName = "numpy-1.24.0-2-cp311-cp311-win_amd64.whl"
+            .try_into()
+            .unwrap();
+        assert_eq!(n.build_number, Some(2));
+        assert_eq!(n.build_name, "");
+        assert_eq!(n.arch_tags, vec!["win_amd64"]);
+        assert_eq!(n.to_string(), "numpy-1.24.0-2-cp311-cp311-win_amd64.whl");
+    }
+
+    #[test]
+    fn test_wheel_name_errors() {
+        fn err(s: &str) -> String {
+            format!("{:#}", WheelName::try_from(s).unwrap_err())
+        }
+
+        insta::assert_snapshot!(
+            err("numpy-1.24.0-cp311-cp311-win_amd64.zip"),
+            @r###"expected "numpy-1.24.0-cp311-cp311-win_amd64.zip" to end in .whl"###
+        );
+        insta::assert_snapshot!(
+            err("numpy-1.24.0-cp311-win_amd64.whl"),
+            @r###"can't parse binary name "numpy-1.24.0-cp311-win_amd64.whl": expected 5 or 6 '-'-separated fields"###
+        );
+        insta::assert_snapshot!(
+            err("numpy-1.24.0-1-2-cp311-cp311-win_amd64.whl"),
+            @r###"can't parse binary name "numpy-1.24.0-1-2-cp311-cp311-win_amd64.whl": expected 5 or 6 '-'-separated fields"###
+        );
+        insta::assert_snapshot!(
+            err("numpy-1.24.0-py2.-none-any.whl"),
+            @r###"found empty tag in binary name "numpy-1.24.0-py2.-none-any.whl""###
+        );
+        assert!(WheelName::try_from("numpy-notaversion-py3-none-any.whl").is_err());
+        assert!(WheelName::try_from("num py-1.0-py3-none-any.whl").is_err());
     }
 
     #[test]
@@ -440,6 +996,7 @@ mod test {
         assert_eq!(n.build_name, "local");
         assert_eq!(n.arch_tags, vec!["win32", "win_amd64"]);
 
-        assert_eq!(n.to_string(), "foo.bar-0.1b3-1local-win32.win_amd64.pybi");
+        // the distribution name gets escaped on the way out, like for wheels
+        assert_eq!(n.to_string(), "foo_bar-0.1b3-1local-win32.win_amd64.pybi");
     }
 }
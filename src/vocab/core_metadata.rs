@@ -4,18 +4,36 @@ use super::rfc822ish::RFC822ish;
 
 /// There are more fields we could add here, but this should be good enough to
 /// get started.
-#[derive(Debug, Clone)]
-#[cfg_attr(test, derive(Serialize))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(all(test, feature = "serde"), derive(Serialize))]
 pub struct WheelCoreMetadata {
+    pub metadata_version: Version,
     pub name: PackageName,
     pub version: Version,
     pub requires_dist: Vec<PackageRequirement>,
     pub requires_python: Specifiers,
     pub extras: HashSet<Extra>,
+    // PEP 643: fields that a build backend may fill in differently when building a
+    // wheel from this sdist. Only meaningful in PKG-INFO.
+    pub dynamic: Vec<String>,
+    // (label, url) pairs, in the order they appeared
+    pub project_urls: Vec<(String, String)>,
+    // Free-form legacy License field
+    pub license: Option<String>,
+    // Metadata 2.4: an SPDX expression, plus paths to license files inside the
+    // distribution. We don't validate the SPDX grammar.
+    pub license_expression: Option<String>,
+    pub license_files: Vec<String>,
+    // Trove classifiers, like "Programming Language :: Python :: 3.11", in the order
+    // they appeared
+    pub classifiers: Vec<String>,
+    // Metadata 2.1+ puts this in the message body; older files use a Description
+    // header.
+    pub description: Option<String>,
 }
 
 #[derive(Debug, Clone)]
-#[cfg_attr(test, derive(Serialize))]
+#[cfg_attr(all(test, feature = "serde"), derive(Serialize))]
 pub struct PybiCoreMetadata {
     pub name: PackageName,
     pub version: Version,
@@ -33,7 +51,50 @@ impl PybiCoreMetadata {
     }
 }
 
-fn parse_common(input: &[u8]) -> Result<(PackageName, Version, RFC822ish)> {
+/// What to do when a METADATA file uses a field that its declared Metadata-Version
+/// doesn't allow.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MetadataStrictness {
+    /// Log a warning and keep going. Real-world metadata is messy enough that this is
+    /// the default.
+    Lenient,
+    /// Fail to parse.
+    Strict,
+}
+
+// Fields that only exist starting from some metadata version. Anything not listed
+// here either predates 1.2 or is something we don't know about, in which case we
+// don't check it.
+static VERSIONED_FIELDS: &[(&str, &str)] = &[
+    ("Description-Content-Type", "2.1"),
+    ("Provides-Extra", "2.1"),
+    ("Dynamic", "2.2"),
+    ("License-Expression", "2.4"),
+    ("License-File", "2.4"),
+];
+
+fn check_versioned_fields(
+    metadata_version: &Version,
+    parsed: &RFC822ish,
+    strictness: MetadataStrictness,
+) -> Result<()> {
+    for (field, since) in VERSIONED_FIELDS {
+        let since: Version = (*since).try_into().unwrap();
+        if *metadata_version < since && parsed.contains(field) {
+            let msg = format!(
+                "field {field} requires Metadata-Version {since}, but this is \
+                 Metadata-Version {metadata_version}"
+            );
+            match strictness {
+                MetadataStrictness::Lenient => warn!("{}", msg),
+                MetadataStrictness::Strict => bail!(msg),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn parse_common(input: &[u8]) -> Result<(Version, PackageName, Version, RFC822ish)> {
     let input = String::from_utf8_lossy(input);
     let mut parsed = RFC822ish::parse(&input)?;
 
@@ -63,26 +124,72 @@ fn parse_common(input: &[u8]) -> Result<(PackageName, Version, RFC822ish)> {
     }
 
     Ok((
+        metadata_version,
         parsed.take_the("Name")?.parse()?,
         parsed.take_the("Version")?.try_into()?,
         parsed,
     ))
 }
 
-impl TryFrom<&[u8]> for WheelCoreMetadata {
-    type Error = eyre::Report;
+// RFC822ish leaves continuation lines embedded in the field value, newlines and
+// all. For fields that are a single logical line, like requirements, fold them back
+// together by dropping the line breaks (the leading whitespace on each continuation
+// line is kept, so tokens stay separated).
+fn unfold(value: &str) -> String {
+    value.lines().collect()
+}
 
-    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
-        let (name, version, mut parsed) = parse_common(value)?;
+// Old-style Description headers put the whole long description into a folded
+// header. The spec says each continuation line starts with 7 spaces and a "|";
+// setuptools in practice uses 8 spaces. Either way, strip the indentation back off
+// (like textwrap.dedent, which is what the Python tools do), and the "|" if present.
+fn unfold_description(value: &str) -> String {
+    let mut lines = value.lines();
+    let first = lines.next().unwrap_or("").trim_start();
+    let rest: Vec<&str> = lines.collect();
+    let indent = rest
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start_matches([' ', '\t']).len())
+        .min()
+        .unwrap_or(0);
+    let mut out = first.to_owned();
+    for line in rest {
+        out.push('\n');
+        let line = line.get(indent..).unwrap_or("");
+        out.push_str(line.strip_prefix('|').unwrap_or(line));
+    }
+    out
+}
+
+impl WheelCoreMetadata {
+    /// Parses the contents of a METADATA (or PKG-INFO) file. Fields we don't use are
+    /// ignored; a missing Metadata-Version, Name, or Version is an error.
+    pub fn parse(input: &[u8]) -> Result<WheelCoreMetadata> {
+        WheelCoreMetadata::parse_with(input, MetadataStrictness::Lenient)
+    }
+
+    /// Like `parse`, but lets you choose what happens when a field shows up that
+    /// isn't allowed by the file's Metadata-Version.
+    pub fn parse_with(
+        input: &[u8],
+        strictness: MetadataStrictness,
+    ) -> Result<WheelCoreMetadata> {
+        let (metadata_version, name, version, mut parsed) = parse_common(input)?;
+        check_versioned_fields(&metadata_version, &parsed, strictness)?;
 
         let mut requires_dist = Vec::new();
         for req_str in parsed.take_all("Requires-Dist").drain(..) {
-            requires_dist.push(req_str.try_into()?);
+            requires_dist.push(
+                unfold(&req_str).as_str().try_into().wrap_err_with(|| {
+                    format!("invalid Requires-Dist: {:?}", req_str)
+                })?,
+            );
         }
 
         let requires_python = match parsed.maybe_take_the("Requires-Python")? {
-            Some(rp_str) => rp_str.try_into()?,
-            None => Specifiers(Vec::new()),
+            Some(rp_str) => Specifiers::parse(&rp_str, SpecifierStrictness::Lenient)?,
+            None => Specifiers::empty(),
         };
 
         let mut extras: HashSet<Extra> = HashSet::new();
@@ -90,21 +197,222 @@ impl TryFrom<&[u8]> for WheelCoreMetadata {
             extras.insert(extra.parse()?);
         }
 
+        let dynamic = parsed.take_all("Dynamic");
+        for field in &dynamic {
+            if ["name", "version", "metadata-version"]
+                .contains(&field.to_ascii_lowercase().as_str())
+            {
+                bail!("field {field} can't be marked as Dynamic");
+            }
+        }
+
+        let mut project_urls = Vec::new();
+        for value in parsed.take_all("Project-URL") {
+            let (label, url) = value.split_once(',').ok_or_else(|| {
+                eyre!("invalid Project-URL {value:?}: expected 'label, url'")
+            })?;
+            project_urls.push((label.trim().to_owned(), url.trim().to_owned()));
+        }
+
+        let license = parsed.maybe_take_the("License")?;
+        let license_expression = parsed.maybe_take_the("License-Expression")?;
+        let license_files = parsed.take_all("License-File");
+        let classifiers = parsed.take_all("Classifier");
+
+        let description = match parsed.body.take() {
+            Some(body) => Some(body),
+            None => parsed
+                .maybe_take_the("Description")?
+                .map(|d| unfold_description(&d)),
+        };
+
         Ok(WheelCoreMetadata {
+            metadata_version,
             name,
             version,
             requires_dist,
             requires_python,
             extras,
+            dynamic,
+            project_urls,
+            license,
+            license_expression,
+            license_files,
+            classifiers,
+            description,
         })
     }
+
+    /// The dependencies of this package when it's installed with the given extras,
+    /// with the extras already substituted into their markers. Extras can pull in
+    /// other extras of the same package (e.g. `all` requiring `pkg[test,docs]`), so we
+    /// keep expanding until there's nothing new; those self-references aren't included
+    /// in the result.
+    pub fn requirements_for_extras(
+        &self,
+        extras: &[Extra],
+    ) -> Result<Vec<PackageRequirement>> {
+        let mut active: Vec<Extra> = Vec::new();
+        let mut pending: Vec<Extra> = extras.to_vec();
+        while let Some(extra) = pending.pop() {
+            if active.contains(&extra) {
+                continue;
+            }
+            if !self.extras.contains(&extra) {
+                warn!(
+                    "{} doesn't provide the extra '{}'",
+                    self.name.as_given(),
+                    extra
+                );
+            }
+            active.push(extra);
+            for req in &self.requires_dist {
+                if req.name != self.name {
+                    continue;
+                }
+                if let Some(req) = req.with_extras(&active)? {
+                    pending.extend(req.extras.iter().cloned());
+                }
+            }
+        }
+        let mut result = Vec::new();
+        for req in &self.requires_dist {
+            if req.name == self.name {
+                continue;
+            }
+            if let Some(req) = req.with_extras(&active)? {
+                result.push(req);
+            }
+        }
+        Ok(result)
+    }
+
+    /// The requirements that don't depend on any extra, i.e. what you get when you
+    /// install the package plain. Their markers may still depend on the environment.
+    pub fn base_requires_dist(&self) -> Vec<&PackageRequirement> {
+        self.requires_dist
+            .iter()
+            .filter(|req| !mentions_extra(req))
+            .collect()
+    }
+
+    /// The requirements that `extra` adds on top of `base_requires_dist`: those whose
+    /// marker checks 'extra' and comes out true-or-undecided for this one. They're
+    /// returned as written, so their markers still mention 'extra'; use
+    /// `requirements_for_extras` to get them with the extras substituted in.
+    pub fn requires_dist_for_extra(&self, extra: &Extra) -> Vec<&PackageRequirement> {
+        self.requires_dist
+            .iter()
+            .filter(|req| {
+                mentions_extra(req)
+                    && req
+                        .with_extras(std::slice::from_ref(extra))
+                        .is_ok_and(|req| req.is_some())
+            })
+            .collect()
+    }
+
+    /// The classifiers that name a specific Python version, like
+    /// "Programming Language :: Python :: 3.11" or
+    /// "Programming Language :: Python :: 3 :: Only". Old packages often have these
+    /// but no Requires-Python, so they're a hint about which Pythons are supported.
+    pub fn python_version_classifiers(&self) -> Vec<String> {
+        self.classifiers
+            .iter()
+            .filter(|c| {
+                c.strip_prefix("Programming Language :: Python :: ")
+                    .is_some_and(|rest| {
+                        rest.starts_with(|ch: char| ch.is_ascii_digit())
+                    })
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Writes this back out in METADATA format, with fields in a fixed order and the
+    /// description (if any) as the message body. Parsing the result gives back an
+    /// equal WheelCoreMetadata.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = String::new();
+        let mut field = |name: &str, value: &dyn Display| {
+            out.push_str(&format!("{name}: {}\n", fold(&value.to_string())));
+        };
+        field("Metadata-Version", &self.metadata_version);
+        field("Name", &self.name.as_given());
+        field("Version", &self.version);
+        for dynamic in &self.dynamic {
+            field("Dynamic", dynamic);
+        }
+        for (label, url) in &self.project_urls {
+            field("Project-URL", &format!("{label}, {url}"));
+        }
+        if let Some(license) = &self.license {
+            field("License", license);
+        }
+        if let Some(license_expression) = &self.license_expression {
+            field("License-Expression", license_expression);
+        }
+        for license_file in &self.license_files {
+            field("License-File", license_file);
+        }
+        for classifier in &self.classifiers {
+            field("Classifier", classifier);
+        }
+        if !self.requires_python.is_empty() {
+            field("Requires-Python", &self.requires_python);
+        }
+        for req in &self.requires_dist {
+            field("Requires-Dist", req);
+        }
+        // HashSet iteration order is arbitrary, so sort to keep output stable
+        let mut extras: Vec<&str> = self.extras.iter().map(|e| e.as_given()).collect();
+        extras.sort();
+        for extra in extras {
+            field("Provides-Extra", &extra);
+        }
+        if let Some(description) = &self.description {
+            out.push('\n');
+            out.push_str(description);
+        }
+        out.into_bytes()
+    }
+}
+
+// Makes a header value with embedded newlines (a multi-line License, say) safe to
+// write out: every line after the first becomes a continuation line, indented by 8
+// spaces like setuptools and wheel do. Lines that are already indented (e.g. because
+// we parsed them that way) are left alone, so parse -> to_bytes -> parse is stable.
+fn fold(value: &str) -> String {
+    let mut lines = value.trim_end_matches(['\r', '\n']).lines();
+    let mut folded = lines.next().unwrap_or("").to_owned();
+    for line in lines {
+        folded.push('\n');
+        if !line.starts_with([' ', '\t']) {
+            folded.push_str("        ");
+        }
+        folded.push_str(line);
+    }
+    folded
+}
+
+fn mentions_extra(req: &PackageRequirement) -> bool {
+    req.marker()
+        .is_some_and(|expr| expr.variables().contains("extra"))
+}
+
+impl TryFrom<&[u8]> for WheelCoreMetadata {
+    type Error = eyre::Report;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        WheelCoreMetadata::parse(value)
+    }
 }
 
 impl TryFrom<&[u8]> for PybiCoreMetadata {
     type Error = eyre::Report;
 
     fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
-        let (name, version, mut parsed) = parse_common(value)?;
+        let (_, name, version, mut parsed) = parse_common(value)?;
 
         Ok(PybiCoreMetadata {
             name,
@@ -124,6 +432,7 @@ mod test {
     use indoc::indoc;
 
     #[test]
+    #[cfg(feature = "serde")]
     fn test_basic_core_parse() {
         let metadata_text = indoc! {r#"
             Metadata-Version: 2.1
@@ -144,20 +453,523 @@ mod test {
 
         insta::assert_ron_snapshot!(metadata, @r###"
         WheelCoreMetadata(
+          metadata_version: "2.1",
           name: "trio",
           version: "0.16.0",
           requires_dist: [
-            "attrs >= 19.2.0",
+            "attrs >=19.2.0",
             "sortedcontainers",
-            "contextvars[foo] >= 2.1; python_version < \"3.7\"",
+            "contextvars[foo] >=2.1; python_version < \"3.7\"",
           ],
-          requires_python: ">= 3.6",
+          requires_python: ">=3.6",
           extras: [],
+          dynamic: [],
+          project_urls: [],
+          license: None,
+          license_expression: None,
+          license_files: [],
+          classifiers: [
+            "Framework :: Trio",
+          ],
+          description: Some("The Trio project\'s goal is...\n"),
+        )
+        "###);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_real_metadata_parse() {
+        // From requests-2.28.1-py3-none-any.whl, with the description trimmed
+        let metadata_text = indoc! {r#"
+            Metadata-Version: 2.1
+            Name: requests
+            Version: 2.28.1
+            Summary: Python HTTP for Humans.
+            Home-page: https://requests.readthedocs.io
+            Author: Kenneth Reitz
+            Author-email: me@kennethreitz.org
+            License: Apache 2.0
+            Project-URL: Documentation, https://requests.readthedocs.io
+            Project-URL: Source, https://github.com/psf/requests
+            Platform: UNKNOWN
+            Classifier: Development Status :: 5 - Production/Stable
+            Classifier: Programming Language :: Python :: 3
+            Requires-Python: >=3.7, <4
+            Description-Content-Type: text/markdown
+            License-File: LICENSE
+            Requires-Dist: charset-normalizer (<3,>=2)
+            Requires-Dist: idna (<4,>=2.5)
+            Requires-Dist: urllib3 (<1.27,>=1.21.1)
+            Requires-Dist: certifi (>=2017.4.17)
+            Provides-Extra: security
+            Provides-Extra: socks
+            Requires-Dist: PySocks (!=1.5.7,>=1.5.6) ; extra == 'socks'
+            Provides-Extra: use_chardet_on_py3
+            Requires-Dist: chardet (<6,>=3.0.2) ; extra == 'use_chardet_on_py3'
+
+            # Requests
+
+            **Requests** is a simple, yet elegant, HTTP library.
+        "#}
+        .as_bytes();
+
+        let metadata = WheelCoreMetadata::parse(metadata_text).unwrap();
+
+        insta::assert_ron_snapshot!(metadata, {".extras" => insta::sorted_redaction()}, @r###"
+        WheelCoreMetadata(
+          metadata_version: "2.1",
+          name: "requests",
+          version: "2.28.1",
+          requires_dist: [
+            "charset-normalizer <3,>=2",
+            "idna <4,>=2.5",
+            "urllib3 <1.27,>=1.21.1",
+            "certifi >=2017.4.17",
+            "PySocks !=1.5.7,>=1.5.6; extra == \"socks\"",
+            "chardet <6,>=3.0.2; extra == \"use_chardet_on_py3\"",
+          ],
+          requires_python: ">=3.7,<4",
+          extras: [
+            Extra("security"),
+            Extra("socks"),
+            Extra("use-chardet-on-py3"),
+          ],
+          dynamic: [],
+          project_urls: [
+            ("Documentation", "https://requests.readthedocs.io"),
+            ("Source", "https://github.com/psf/requests"),
+          ],
+          license: Some("Apache 2.0"),
+          license_expression: None,
+          license_files: [
+            "LICENSE",
+          ],
+          classifiers: [
+            "Development Status :: 5 - Production/Stable",
+            "Programming Language :: Python :: 3",
+          ],
+          description: Some("# Requests\n\n**Requests** is a simple, yet elegant, HTTP library.\n"),
+        )
+        "###);
+    }
+
+    #[test]
+    fn test_requires_dist_parse() {
+        let metadata_text = indoc! {r#"
+            Metadata-Version: 2.1
+            Name: foo
+            Version: 1.0
+            Requires-Dist: foo[bar]>=1.0; python_version < "3.8"
+            Requires-Dist: baz (>=2.0,
+              <3) ; sys_platform == "win32"
+        "#}
+        .as_bytes();
+
+        let metadata = WheelCoreMetadata::parse(metadata_text).unwrap();
+        #[cfg(feature = "serde")]
+        insta::assert_ron_snapshot!(metadata.requires_dist, @r###"
+        [
+          "foo[bar] >=1.0; python_version < \"3.8\"",
+          "baz >=2.0,<3; sys_platform == \"win32\"",
+        ]
+        "###);
+        let extras: Vec<&str> = metadata.requires_dist[0]
+            .extras
+            .iter()
+            .map(|e| e.normalized())
+            .collect();
+        assert_eq!(extras, vec!["bar"]);
+
+        let bad = indoc! {r#"
+            Metadata-Version: 2.1
+            Name: foo
+            Version: 1.0
+            Requires-Dist: sortedcontainers
+            Requires-Dist: attrs >=== 1
+        "#};
+        let err = WheelCoreMetadata::parse(bad.as_bytes()).unwrap_err();
+        assert_eq!(err.to_string(), r#"invalid Requires-Dist: "attrs >=== 1""#);
+    }
+
+    #[test]
+    fn test_requirements_for_extras() {
+        let metadata = WheelCoreMetadata::parse(
+            indoc! {r#"
+                Metadata-Version: 2.1
+                Name: pkg
+                Version: 1.0
+                Requires-Dist: always
+                Requires-Dist: pytest; extra == "test"
+                Requires-Dist: sphinx; extra == "docs" and python_version >= "3.8"
+                Requires-Dist: pkg[test,docs]; extra == "all"
+                Provides-Extra: test
+                Provides-Extra: docs
+                Provides-Extra: all
+            "#}
+            .as_bytes(),
         )
+        .unwrap();
+        let reqs = |extras: &[&str]| -> Vec<String> {
+            let extras: Vec<Extra> =
+                extras.iter().map(|e| (*e).try_into().unwrap()).collect();
+            metadata
+                .requirements_for_extras(&extras)
+                .unwrap()
+                .iter()
+                .map(|r| r.to_string())
+                .collect()
+        };
+        assert_eq!(reqs(&[]), vec!["always"]);
+        assert_eq!(reqs(&["test"]), vec!["always", "pytest"]);
+        assert_eq!(
+            reqs(&["all"]),
+            vec!["always", "pytest", r#"sphinx; python_version >= "3.8""#]
+        );
+    }
+
+    #[test]
+    fn test_metadata_versions() {
+        use MetadataStrictness::*;
+
+        let v21 = indoc! {"
+            Metadata-Version: 2.1
+            Name: foo
+            Version: 1.0
+            Provides-Extra: test
+            Dynamic: Requires-Dist
+        "}
+        .as_bytes();
+        let metadata = WheelCoreMetadata::parse_with(v21, Lenient).unwrap();
+        assert_eq!(metadata.metadata_version, "2.1".try_into().unwrap());
+        assert_eq!(
+            WheelCoreMetadata::parse_with(v21, Strict)
+                .unwrap_err()
+                .to_string(),
+            "field Dynamic requires Metadata-Version 2.2, but this is Metadata-Version 2.1"
+        );
+
+        let v23 = indoc! {"
+            Metadata-Version: 2.3
+            Name: foo
+            Version: 1.0
+            Provides-Extra: test
+            Dynamic: Requires-Dist
+        "}
+        .as_bytes();
+        let metadata = WheelCoreMetadata::parse_with(v23, Strict).unwrap();
+        assert_eq!(metadata.metadata_version, "2.3".try_into().unwrap());
+
+        let v23_with_license_expr = indoc! {"
+            Metadata-Version: 2.3
+            Name: foo
+            Version: 1.0
+            License-Expression: MIT
+        "}
+        .as_bytes();
+        assert!(WheelCoreMetadata::parse_with(v23_with_license_expr, Lenient).is_ok());
+        assert!(WheelCoreMetadata::parse_with(v23_with_license_expr, Strict).is_err());
+    }
+
+    #[test]
+    fn test_dynamic() {
+        let pkg_info = indoc! {"
+            Metadata-Version: 2.2
+            Name: foo
+            Version: 1.0
+            Dynamic: Requires-Dist
+            Dynamic: Requires-Python
+        "};
+        let metadata = WheelCoreMetadata::parse(pkg_info.as_bytes()).unwrap();
+        assert_eq!(metadata.dynamic, vec!["Requires-Dist", "Requires-Python"]);
+
+        for forbidden in ["Name", "version", "Metadata-Version"] {
+            let text = format!("{pkg_info}Dynamic: {forbidden}\n");
+            let err = WheelCoreMetadata::parse(text.as_bytes()).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                format!("field {forbidden} can't be marked as Dynamic")
+            );
+        }
+    }
+
+    #[test]
+    fn test_metadata_to_bytes() {
+        let metadata_text = indoc! {r#"
+            Metadata-Version: 2.1
+            Name: Foo.Bar
+            Version: 1.0
+            Summary: ignored, since we don't keep it
+            License: Copyright (c) Foo Bar
+                    All rights reserved.
+            Requires-Python: >= 3.7
+            Requires-Dist: attrs (>=19.2.0)
+            Provides-Extra: test
+            Requires-Dist: pytest ; extra == 'test'
+            Provides-Extra: docs
+
+            # Foo.Bar
+
+            It's a package.
+        "#}
+        .as_bytes();
+
+        let metadata = WheelCoreMetadata::parse(metadata_text).unwrap();
+        let serialized = metadata.to_bytes();
+        insta::assert_snapshot!(String::from_utf8_lossy(&serialized), @r###"
+        Metadata-Version: 2.1
+        Name: Foo.Bar
+        Version: 1.0
+        License: Copyright (c) Foo Bar
+                All rights reserved.
+        Requires-Python: >=3.7
+        Requires-Dist: attrs >=19.2.0
+        Requires-Dist: pytest; extra == "test"
+        Provides-Extra: docs
+        Provides-Extra: test
+
+        # Foo.Bar
+
+        It's a package.
         "###);
+
+        let reparsed = WheelCoreMetadata::parse(&serialized).unwrap();
+        assert_eq!(reparsed, metadata);
+        assert_eq!(reparsed.to_bytes(), serialized);
+
+        // multi-line values that didn't come from parsing get folded, even across
+        // blank lines, which would otherwise end the headers
+        let mut metadata = metadata;
+        metadata.license = Some("MIT License\n\nPermission is hereby granted".into());
+        let serialized = metadata.to_bytes();
+        assert!(String::from_utf8_lossy(&serialized).contains(
+            "License: MIT License\n        \n        Permission is hereby granted\n"
+        ));
+        let reparsed = WheelCoreMetadata::parse(&serialized).unwrap();
+        let license = reparsed.license.as_deref().unwrap();
+        assert_eq!(
+            license.lines().map(|l| l.trim()).collect::<Vec<_>>(),
+            ["MIT License", "", "Permission is hereby granted"]
+        );
+        assert_eq!(reparsed.description, metadata.description);
+        assert_eq!(reparsed.requires_dist, metadata.requires_dist);
+        assert_eq!(reparsed.to_bytes(), serialized);
+
+        // no body
+        let minimal = indoc! {"
+            Metadata-Version: 2.2
+            Name: foo
+            Version: 1.0
+            Dynamic: Requires-Dist
+        "};
+        let metadata = WheelCoreMetadata::parse(minimal.as_bytes()).unwrap();
+        assert_eq!(metadata.description, None);
+        assert_eq!(String::from_utf8(metadata.to_bytes()).unwrap(), minimal);
+    }
+
+    #[test]
+    fn test_requires_dist_for_extra() {
+        let metadata = WheelCoreMetadata::parse(
+            indoc! {r#"
+                Metadata-Version: 2.1
+                Name: foo
+                Version: 1.0
+                Provides-Extra: test
+                Provides-Extra: Fancy_Docs
+                Requires-Dist: attrs
+                Requires-Dist: tomli; python_version < "3.11"
+                Requires-Dist: pytest; extra == "test"
+                Requires-Dist: hypothesis; extra == 'test' and python_version >= "3.8"
+                Requires-Dist: sphinx; extra == "fancy-docs"
+                Requires-Dist: coverage; extra == "test" or extra == "fancy_docs"
+            "#}
+            .as_bytes(),
+        )
+        .unwrap();
+        let names = |reqs: Vec<&PackageRequirement>| {
+            reqs.into_iter()
+                .map(|r| r.name.as_given().to_owned())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(metadata.base_requires_dist()), ["attrs", "tomli"]);
+        assert_eq!(
+            names(metadata.requires_dist_for_extra(&"test".parse().unwrap())),
+            ["pytest", "hypothesis", "coverage"]
+        );
+        // names are compared normalized
+        assert_eq!(
+            names(metadata.requires_dist_for_extra(&"FANCY.DOCS".parse().unwrap())),
+            ["sphinx", "coverage"]
+        );
+        assert!(metadata
+            .requires_dist_for_extra(&"nope".parse().unwrap())
+            .is_empty());
+    }
+
+    #[test]
+    fn test_classifiers() {
+        let metadata_text = indoc! {"
+            Metadata-Version: 2.1
+            Name: foo
+            Version: 1.0
+            Classifier: Development Status :: 4 - Beta
+            Classifier: Programming Language :: Python
+            Classifier: Programming Language :: Python :: 3
+            Classifier: Programming Language :: Python :: 3 :: Only
+            Classifier: Programming Language :: Python :: 3.10
+            Classifier: Programming Language :: Python :: 3.11
+            Classifier: Programming Language :: Python :: Implementation :: PyPy
+            Classifier: Programming Language :: Rust
+        "};
+        let metadata = WheelCoreMetadata::parse(metadata_text.as_bytes()).unwrap();
+        assert_eq!(metadata.classifiers.len(), 8);
+        assert_eq!(metadata.classifiers[0], "Development Status :: 4 - Beta");
+        assert_eq!(
+            metadata.python_version_classifiers(),
+            vec![
+                "Programming Language :: Python :: 3",
+                "Programming Language :: Python :: 3 :: Only",
+                "Programming Language :: Python :: 3.10",
+                "Programming Language :: Python :: 3.11",
+            ]
+        );
+        assert_eq!(
+            String::from_utf8(metadata.to_bytes()).unwrap(),
+            metadata_text
+        );
+    }
+
+    #[test]
+    fn test_project_urls() {
+        let metadata_text = indoc! {"
+            Metadata-Version: 2.1
+            Name: foo
+            Version: 1.0
+            Project-URL: Homepage, https://example.com
+            Project-URL:Bug Tracker ,https://example.com/issues?q=a,b
+        "};
+        let metadata = WheelCoreMetadata::parse(metadata_text.as_bytes()).unwrap();
+        assert_eq!(
+            metadata.project_urls,
+            vec![
+                ("Homepage".to_owned(), "https://example.com".to_owned()),
+                (
+                    "Bug Tracker".to_owned(),
+                    "https://example.com/issues?q=a,b".to_owned()
+                ),
+            ]
+        );
+
+        let bad = format!("{metadata_text}Project-URL: https://example.com/oops\n");
+        let err = WheelCoreMetadata::parse(bad.as_bytes()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            r#"invalid Project-URL "https://example.com/oops": expected 'label, url'"#
+        );
+    }
+
+    #[test]
+    fn test_license_fields() {
+        let metadata_text = indoc! {"
+            Metadata-Version: 2.4
+            Name: foo
+            Version: 1.0
+            License-Expression: MIT
+            License-File: LICENSE
+            License-File: licenses/vendored/LICENSE.txt
+        "};
+        let metadata = WheelCoreMetadata::parse_with(
+            metadata_text.as_bytes(),
+            MetadataStrictness::Strict,
+        )
+        .unwrap();
+        assert_eq!(metadata.license, None);
+        assert_eq!(metadata.license_expression.as_deref(), Some("MIT"));
+        assert_eq!(
+            metadata.license_files,
+            vec!["LICENSE", "licenses/vendored/LICENSE.txt"]
+        );
+        assert_eq!(
+            String::from_utf8(metadata.to_bytes()).unwrap(),
+            metadata_text
+        );
+
+        let legacy = indoc! {"
+            Metadata-Version: 2.1
+            Name: foo
+            Version: 1.0
+            License: BSD-ish, see LICENSE.txt
+        "};
+        let metadata = WheelCoreMetadata::parse(legacy.as_bytes()).unwrap();
+        assert_eq!(
+            metadata.license.as_deref(),
+            Some("BSD-ish, see LICENSE.txt")
+        );
+        assert_eq!(metadata.license_expression, None);
+    }
+
+    #[test]
+    fn test_folded_description_header() {
+        // written out by hand, because the whitespace-only continuation lines matter
+        let setuptools_style = concat!(
+            "Metadata-Version: 1.2\n",
+            "Name: foo\n",
+            "Version: 1.0\n",
+            "Description: Foo\n",
+            "        ===\n",
+            "        \n",
+            "        Indented:\n",
+            "        \n",
+            "            code\n",
+            "Requires-Python: >=3\n",
+        );
+        let metadata = WheelCoreMetadata::parse(setuptools_style.as_bytes()).unwrap();
+        assert_eq!(
+            metadata.description.as_deref(),
+            Some("Foo\n===\n\nIndented:\n\n    code")
+        );
+        assert_eq!(metadata.requires_python.to_string(), ">=3");
+
+        let spec_style = concat!(
+            "Metadata-Version: 1.2\n",
+            "Name: foo\n",
+            "Version: 1.0\n",
+            "Description: a\n",
+            "       |b\n",
+            "       |  c\n",
+        );
+        let metadata = WheelCoreMetadata::parse(spec_style.as_bytes()).unwrap();
+        assert_eq!(metadata.description.as_deref(), Some("a\nb\n  c"));
+    }
+
+    #[test]
+    fn test_metadata_missing_required_fields() {
+        let complete = indoc! {"
+            Metadata-Version: 2.1
+            Name: trio
+            Version: 0.16.0
+        "};
+        assert!(WheelCoreMetadata::parse(complete.as_bytes()).is_ok());
+
+        for (field, expected) in [
+            (
+                "Metadata-Version",
+                "can't find required key Metadata-Version",
+            ),
+            ("Name", "can't find required key Name"),
+            ("Version", "can't find required key Version"),
+        ] {
+            let text: String = complete
+                .lines()
+                .filter(|line| !line.starts_with(&format!("{field}:")))
+                .map(|line| format!("{line}\n"))
+                .collect();
+            let err = WheelCoreMetadata::parse(text.as_bytes()).unwrap_err();
+            assert_eq!(err.to_string(), expected);
+        }
     }
 
     #[test]
+    #[cfg(feature = "serde")]
     fn test_basic_pybi_parse() {
         let metadata_text = indoc! {r#"
             Metadata-Version: 2.1
@@ -181,7 +993,7 @@ mod test {
             },
                                     @r###"
         PybiCoreMetadata(
-          name: "CPython",
+          name: "cpython",
           version: "3.11.2",
           environment_marker_variables: {
             "implementation_name": "cpython",
@@ -10,14 +10,17 @@
 // be "identifiers", which means: first char [A-Za-z0-9], remaining chars also
 // allowed to include -_.
 //
-// I guess for now I'll just pretend that they act the same as package names,
-// and see how long I can get away with it.
+// PEP 685 finally settled this: extras are normalized exactly like package names
+// (lowercase, each run of -_. becomes a single -), and compared in that form. So
+// we reuse PackageName, which gives us PEP 503 normalization plus PartialEq/Hash
+// over the normalized form.
 //
 // There's probably a better way to factor this and reduce code duplication...
 
 use crate::prelude::*;
 
-#[derive(Debug, Clone, Deserialize, Serialize, Hash, PartialEq, Eq)]
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct Extra(PackageName);
 
 impl Extra {
@@ -40,3 +43,35 @@ impl TryFrom<&str> for Extra {
 }
 
 try_from_str_boilerplate!(Extra);
+
+impl Display for Extra {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.normalized())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_extra_normalization() {
+        let canonical: Extra = "foo-bar".try_into().unwrap();
+        for variant in ["foo-bar", "Foo_Bar", "FOO.BAR", "foo__bar", "foo-_.bar"] {
+            let extra: Extra = variant.try_into().unwrap();
+            assert_eq!(extra, canonical);
+            assert_eq!(extra.as_given(), variant);
+            assert_eq!(extra.normalized(), "foo-bar");
+            assert_eq!(extra.to_string(), "foo-bar");
+        }
+
+        let other: Extra = "foobar".try_into().unwrap();
+        assert_ne!(other, canonical);
+
+        let extras: HashSet<Extra> = ["Foo_Bar", "foo.bar", "foobar"]
+            .into_iter()
+            .map(|s| s.try_into().unwrap())
+            .collect();
+        assert_eq!(extras.len(), 2);
+    }
+}
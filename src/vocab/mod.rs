@@ -1,3 +1,5 @@
+#[cfg(any(test, feature = "arbitrary"))]
+pub mod arbitrary;
 mod artifact_formats;
 mod artifact_hash;
 mod artifact_name;
@@ -5,8 +7,10 @@ mod core_metadata;
 mod entry_points;
 mod extra;
 mod package_name;
+mod record;
 mod reqparse;
 mod requirement;
+mod requirements_txt;
 mod rfc822ish;
 mod specifier;
 mod version;
@@ -27,5 +31,7 @@ pub use self::requirement::{
     marker, PackageRequirement, PythonRequirement, Requirement, StandaloneMarkerExpr,
     UserRequirement,
 };
-pub use self::specifier::{CompareOp, Specifier, Specifiers};
+pub use self::specifier::{
+    CompareOp, RequiresPython, Specifier, SpecifierStrictness, Specifiers,
+};
 pub use self::version::{Version, VERSION_INFINITY, VERSION_ZERO};
@@ -1,21 +1,94 @@
 use crate::prelude::*;
 
-#[derive(Debug, Clone, DeserializeFromStr, Derivative)]
+/// A Python package name.
+///
+/// Remembers the spelling it was created from, but equality, ordering, and hashing
+/// all use only the PEP 503 normalized form, so e.g. `Foo.Bar` and `foo-bar` are
+/// the same name (and the same `HashMap` key).
+#[derive(Debug, Clone, Derivative)]
+#[cfg_attr(feature = "serde", derive(DeserializeFromStr))]
 #[derivative(Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub struct PackageName {
-    #[derivative(Hash = "ignore", PartialEq = "ignore", PartialOrd = "ignore")]
+    #[derivative(
+        Hash = "ignore",
+        PartialEq = "ignore",
+        PartialOrd = "ignore",
+        Ord = "ignore"
+    )]
     as_given: String,
     normalized: String,
 }
 
 impl PackageName {
+    /// The name exactly as it was originally spelled. Use this for showing names to
+    /// users.
     pub fn as_given(&self) -> &str {
         &self.as_given
     }
 
+    /// The PEP 503 normalized name: lowercased, with each run of `-`, `_`, and `.`
+    /// collapsed into a single `-`. Use this for comparisons and lookups.
     pub fn normalized(&self) -> &str {
         &self.normalized
     }
+
+    /// The name escaped for use as the distribution component of a wheel or sdist
+    /// filename (PEP 427): each run of non-alphanumeric characters becomes a single
+    /// `_`, so `My.Package-Name` becomes `My_Package_Name`. Case is preserved.
+    pub fn wheel_distribution_name(&self) -> String {
+        static WHEEL_ESCAPE: Lazy<Regex> =
+            Lazy::new(|| Regex::new(r"[^A-Za-z0-9]+").unwrap());
+        WHEEL_ESCAPE.replace_all(&self.as_given, "_").into_owned()
+    }
+}
+
+// The global table behind `PackageName::intern`. Entries are never removed, so we
+// leak them and hand out &'static references.
+#[derive(Default)]
+struct InternTable {
+    ids: HashMap<&'static str, u32>,
+    names: Vec<&'static PackageName>,
+}
+
+static INTERN_TABLE: Lazy<std::sync::Mutex<InternTable>> = Lazy::new(Default::default);
+
+/// A handle to an interned `PackageName`; see `PackageName::intern`. Comparing and
+/// hashing these is just comparing and hashing integers, so they're good for hot
+/// paths like the resolver, where the same names get compared over and over.
+///
+/// Ordering follows the order names were first interned, not the names themselves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct InternedName(u32);
+
+impl InternedName {
+    /// The interned name. If it was interned under several spellings, this is
+    /// whichever one came first.
+    pub fn get(self) -> &'static PackageName {
+        INTERN_TABLE.lock().unwrap().names[self.0 as usize]
+    }
+}
+
+impl Display for InternedName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.get().as_given())
+    }
+}
+
+impl PackageName {
+    /// Returns a small handle that compares equal to the handle for any other
+    /// spelling of the same name. The name was already normalized when it was
+    /// parsed, so all this costs is one table lookup.
+    pub fn intern(&self) -> InternedName {
+        let mut table = INTERN_TABLE.lock().unwrap();
+        if let Some(id) = table.ids.get(self.normalized()) {
+            return InternedName(*id);
+        }
+        let id = u32::try_from(table.names.len()).expect("too many interned names");
+        let name: &'static PackageName = Box::leak(Box::new(self.clone()));
+        table.ids.insert(name.normalized(), id);
+        table.names.push(name);
+        InternedName(id)
+    }
 }
 
 impl TryFrom<&str> for PackageName {
@@ -28,8 +101,12 @@ impl TryFrom<&str> for PackageName {
         });
         // https://www.python.org/dev/peps/pep-0503/#normalized-names
         static NAME_NORMALIZE: Lazy<Regex> =
-            Lazy::new(|| Regex::new(r"[-_.]").unwrap());
+            Lazy::new(|| Regex::new(r"[-_.]+").unwrap());
 
+        // Stray whitespace around a name (e.g. "Name:  foo " in hand-edited
+        // metadata) is harmless, so drop it. Whitespace inside a name is still an
+        // error.
+        let as_given = as_given.trim();
         if !NAME_VALIDATE.is_match(as_given) {
             return Err(eyre!("Invalid package name {:?}", as_given));
         }
@@ -47,12 +124,15 @@ impl TryFrom<&str> for PackageName {
 
 try_from_str_boilerplate!(PackageName);
 
+// Serialize the normalized form, so that e.g. lockfiles don't change depending on how
+// someone happened to spell a name. Deserializing it gives back an equal PackageName.
+#[cfg(feature = "serde")]
 impl Serialize for PackageName {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: serde::Serializer,
     {
-        serializer.serialize_str(self.as_given())
+        serializer.serialize_str(self.normalized())
     }
 }
 
@@ -76,6 +156,37 @@ mod test {
         assert_ne!(name1, name3);
     }
 
+    #[test]
+    fn test_packagename_normalization() {
+        for (given, normalized) in [
+            ("Django", "django"),
+            ("zope.interface", "zope-interface"),
+            ("back__slash", "back-slash"),
+            ("Foo-._Bar", "foo-bar"),
+        ] {
+            let name: PackageName = given.try_into().unwrap();
+            assert_eq!(name.as_given(), given);
+            assert_eq!(name.normalized(), normalized);
+        }
+    }
+
+    #[test]
+    fn test_wheel_distribution_name() {
+        for (given, escaped) in [
+            ("My.Package-Name", "My_Package_Name"),
+            ("trio", "trio"),
+            ("zope.interface", "zope_interface"),
+            ("foo-._bar", "foo_bar"),
+            ("already_escaped", "already_escaped"),
+        ] {
+            let name: PackageName = given.try_into().unwrap();
+            assert_eq!(name.wheel_distribution_name(), escaped);
+            // the escaped form is still the same package
+            let reparsed: PackageName = escaped.try_into().unwrap();
+            assert_eq!(reparsed, name);
+        }
+    }
+
     #[test]
     fn test_packagename_validation() {
         let name: Result<PackageName> = "foobar baz".try_into();
@@ -83,9 +194,24 @@ mod test {
 
         let name: Result<PackageName> = "foobarbaz!".parse();
         assert!(name.is_err());
+
+        for bad in ["", "   ", "foo\tbar", " foo bar "] {
+            let name: Result<PackageName> = bad.try_into();
+            assert!(name.is_err(), "{:?}", bad);
+        }
     }
 
     #[test]
+    fn test_packagename_surrounding_whitespace() {
+        for padded in [" Foo.Bar", "Foo.Bar ", "\tFoo.Bar\n", "  Foo.Bar  "] {
+            let name: PackageName = padded.try_into().unwrap();
+            assert_eq!(name.as_given(), "Foo.Bar");
+            assert_eq!(name.normalized(), "foo-bar");
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
     fn test_packagename_serde() {
         let direct: PackageName = "foo-bar_baz".try_into().unwrap();
         let via_serde: Vec<PackageName> =
@@ -97,6 +223,11 @@ mod test {
         let bad: serde_json::Result<PackageName> =
             serde_json::from_str(r#" "foo bar" "#);
         assert!(bad.is_err());
+
+        let json = serde_json::to_string(&direct).unwrap();
+        assert_eq!(json, r#""foo-bar-baz""#);
+        let roundtrip: PackageName = serde_json::from_str(&json).unwrap();
+        assert_eq!(roundtrip, direct);
     }
 
     #[test]
@@ -121,4 +252,50 @@ mod test {
         // re-sampled when Rust changes their hash function. So I think we're safe.
         assert_ne!(calculate_hash(&name1), calculate_hash(&name_other));
     }
+
+    #[test]
+    fn test_packagename_intern() {
+        let name = |s: &str| -> PackageName { s.try_into().unwrap() };
+        let a = name("Intern-Test.Name").intern();
+        let b = name("intern_test_name").intern();
+        let other = name("intern-test-other").intern();
+        assert_eq!(a, b);
+        assert_ne!(a, other);
+        // interning again gives back the same handle
+        assert_eq!(name("INTERN.TEST.NAME").intern(), a);
+
+        // the first spelling wins for display
+        assert_eq!(a.get().as_given(), "Intern-Test.Name");
+        assert_eq!(b.to_string(), "Intern-Test.Name");
+        assert_eq!(b.get(), &name("intern-test-name"));
+
+        // The point: once interned, comparisons never touch the strings. Run enough of
+        // them that re-normalizing each time would be very visible in a profile.
+        let handles: Vec<InternedName> =
+            (0..1000).map(|i| if i % 2 == 0 { a } else { b }).collect();
+        let mut equal = 0;
+        for _ in 0..1000 {
+            equal += handles.iter().filter(|h| **h == a).count();
+        }
+        assert_eq!(equal, 1_000_000);
+        assert_eq!(std::mem::size_of::<InternedName>(), 4);
+    }
+
+    #[test]
+    fn test_packagename_as_map_key() {
+        let name1: PackageName = "Foo.Bar".try_into().unwrap();
+        let name2: PackageName = "foo-bar".try_into().unwrap();
+        assert_eq!(name1, name2);
+        assert_eq!(name1.cmp(&name2), std::cmp::Ordering::Equal);
+
+        let mut map = HashMap::new();
+        map.insert(name1, 1);
+        map.insert(name2, 2);
+        assert_eq!(map.len(), 1);
+        let (key, value) = map.iter().next().unwrap();
+        // HashMap::insert keeps the original key
+        assert_eq!(key.as_given(), "Foo.Bar");
+        assert_eq!(*value, 2);
+        assert!(map.contains_key(&"FOO_BAR".parse::<PackageName>().unwrap()));
+    }
 }
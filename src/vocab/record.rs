@@ -0,0 +1,227 @@
+use crate::prelude::*;
+
+// The RECORD file in a wheel's .dist-info lists every file in the wheel, as CSV rows
+// of path,hash,size:
+//
+//   https://packaging.python.org/specifications/recording-installed-packages/#the-record-file
+//
+// The hash is "{algorithm}={urlsafe-base64-nopad digest}". The row for RECORD itself
+// (and for any .pyc files written at install time) leaves the hash and size empty.
+//
+// It's written with Python's csv module in its default dialect, so fields containing
+// commas, quotes, or newlines get double-quoted with "" as the escape for ". We don't
+// have a CSV crate, and the format is small enough to just handle here.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordEntry {
+    pub path: String,
+    /// (algorithm, urlsafe-base64 digest)
+    pub hash: Option<(String, String)>,
+    pub size: Option<u64>,
+}
+
+impl RecordEntry {
+    /// Checks `data` against the recorded hash and size. Returns Ok(false) on a
+    /// mismatch, and an error if the entry has no hash or uses a hash algorithm we
+    /// don't know, since then we can't actually vouch for the data.
+    pub fn verify(&self, data: &[u8]) -> Result<bool> {
+        let (algorithm, expected) = self
+            .hash
+            .as_ref()
+            .ok_or_else(|| eyre!("no hash recorded for {}", self.path))?;
+        let algorithm = match algorithm.as_str() {
+            "sha256" => &ring::digest::SHA256,
+            "sha384" => &ring::digest::SHA384,
+            "sha512" => &ring::digest::SHA512,
+            _ => bail!("unsupported hash algorithm {} for {}", algorithm, self.path),
+        };
+        if let Some(size) = self.size {
+            if size != data.len() as u64 {
+                return Ok(false);
+            }
+        }
+        let digest = ring::digest::digest(algorithm, data);
+        let got = data_encoding::BASE64URL_NOPAD.encode(digest.as_ref());
+        // The spec says no padding, but be forgiving if someone added it anyway
+        Ok(got == expected.trim_end_matches('='))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Record {
+    pub entries: Vec<RecordEntry>,
+}
+
+impl Record {
+    pub fn parse(csv: &str) -> Result<Record> {
+        let mut entries = Vec::new();
+        for (lineno, row) in csv_rows(csv)?.into_iter().enumerate() {
+            context!("parsing RECORD row {}", lineno + 1);
+            let [path, hash, size]: [String; 3] =
+                row.try_into().map_err(|row: Vec<String>| {
+                    eyre!("expected 3 fields, got {}", row.len())
+                })?;
+            if path.is_empty() {
+                bail!("empty path");
+            }
+            let hash = if hash.is_empty() {
+                None
+            } else {
+                let (algorithm, value) = hash
+                    .split_once('=')
+                    .ok_or_else(|| eyre!("expected = in hash {:?}", hash))?;
+                Some((algorithm.to_owned(), value.to_owned()))
+            };
+            let size = if size.is_empty() {
+                None
+            } else {
+                Some(
+                    size.parse()
+                        .wrap_err_with(|| format!("invalid size {:?}", size))?,
+                )
+            };
+            entries.push(RecordEntry { path, hash, size });
+        }
+        Ok(Record { entries })
+    }
+}
+
+// Splits CSV text into rows of fields. Blank lines are skipped.
+fn csv_rows(input: &str) -> Result<Vec<Vec<String>>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    // whether the current row has anything in it yet, so we can skip blank lines
+    let mut row_started = false;
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if field.is_empty() => {
+                row_started = true;
+                loop {
+                    match chars.next() {
+                        None => bail!("unterminated quoted field in CSV"),
+                        Some('"') if chars.peek() == Some(&'"') => {
+                            chars.next();
+                            field.push('"');
+                        }
+                        Some('"') => break,
+                        Some(c) => field.push(c),
+                    }
+                }
+            }
+            ',' => {
+                row_started = true;
+                row.push(std::mem::take(&mut field));
+            }
+            '\r' | '\n' => {
+                if c == '\r' && chars.peek() == Some(&'\n') {
+                    chars.next();
+                }
+                if row_started {
+                    row.push(std::mem::take(&mut field));
+                    rows.push(std::mem::take(&mut row));
+                    row_started = false;
+                }
+            }
+            c => {
+                row_started = true;
+                field.push(c);
+            }
+        }
+    }
+    if row_started {
+        row.push(field);
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn test_record_parse() {
+        let record = Record::parse(indoc! {r#"
+            trio/__init__.py,sha256=5yn3lmEnZoAaDfk6YCy5VwJhWDMFrYHmhVg5gBF5hVk,3872
+            "odd,name.py",sha256=AAAA,0
+            trio-0.22.0.dist-info/RECORD,,
+        "#})
+        .unwrap();
+        assert_eq!(
+            record.entries,
+            vec![
+                RecordEntry {
+                    path: "trio/__init__.py".into(),
+                    hash: Some((
+                        "sha256".into(),
+                        "5yn3lmEnZoAaDfk6YCy5VwJhWDMFrYHmhVg5gBF5hVk".into()
+                    )),
+                    size: Some(3872),
+                },
+                RecordEntry {
+                    path: "odd,name.py".into(),
+                    hash: Some(("sha256".into(), "AAAA".into())),
+                    size: Some(0),
+                },
+                RecordEntry {
+                    path: "trio-0.22.0.dist-info/RECORD".into(),
+                    hash: None,
+                    size: None,
+                },
+            ]
+        );
+
+        // CRLF line endings and no trailing newline are fine too
+        let record = Record::parse("a,,\r\nb,,").unwrap();
+        assert_eq!(record.entries.len(), 2);
+
+        assert_eq!(Record::parse("").unwrap(), Record::default());
+    }
+
+    #[test]
+    fn test_record_parse_errors() {
+        for bad in [
+            "a,sha256=AAAA\n",
+            "a,sha256=AAAA,1,extra\n",
+            "a,sha256AAAA,1\n",
+            "a,sha256=AAAA,big\n",
+            "\"a,,\n",
+            ",,\n",
+        ] {
+            println!("{:?}", bad);
+            assert!(Record::parse(bad).is_err());
+        }
+    }
+
+    #[test]
+    fn test_record_verify() {
+        let record = Record::parse(indoc! {"
+            hello.txt,sha256=uU0nuZNNPgilLlLX2n2r-sSE7-N6U4DukIj3rOLvzek,11
+            hello-nosize.txt,sha256=uU0nuZNNPgilLlLX2n2r-sSE7-N6U4DukIj3rOLvzek,
+            empty.txt,sha256=47DEQpj8HBSa-_TImW-5JCeuQeRkm5NMpJWZG3hSuFU=,0
+            hello512.txt,sha512=MJ7MSJwS1utMxA9QyQLytNDtd-5RGnx6m808qG1M2G-YndNbxf9JlnDaNCVbRbDP2DDoH2Bdz33FVC6TrpzXbw,11
+            weird.txt,md5=XrY7u-Ae7tCTyyK7j1rNww,11
+            RECORD,,
+        "})
+        .unwrap();
+        let [hello, hello_nosize, empty, hello512, weird, record_entry] =
+            &record.entries[..]
+        else {
+            panic!("wrong number of entries");
+        };
+
+        assert!(hello.verify(b"hello world").unwrap());
+        assert!(!hello.verify(b"hello world!").unwrap());
+        // same size, different contents
+        assert!(!hello.verify(b"hello there").unwrap());
+        assert!(hello_nosize.verify(b"hello world").unwrap());
+        assert!(empty.verify(b"").unwrap());
+        assert!(hello512.verify(b"hello world").unwrap());
+
+        assert!(weird.verify(b"hello world").is_err());
+        assert!(record_entry.verify(b"").is_err());
+    }
+}
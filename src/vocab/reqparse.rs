@@ -14,26 +14,18 @@ peg::parser! {
         rule _()
             = quiet!{ wsp()* }
 
+        // "===" has to come before "==", or else it'll never match
         rule version_cmp() -> &'input str
-            = $("<=" / "<" / "!=" / "==" / ">=" / ">" / "~=" / "===")
+            = $("<=" / "<" / "!=" / "===" / "==" / ">=" / ">" / "~=")
 
         rule version()
             = (letter_or_digit() / "-" / "_" / "." / "*" / "+" / "!")+
 
         rule version_one() -> Specifier
             = _ op:version_cmp() _ v:$(version())
-           {?
-                // peg requires &str for errors, so we can't pass through the result of
-                // try_from directly
-                if op == "===" {
-                    Err("'===' is not implemented")
-                } else {
-                    Ok(Specifier {
-                        // unwrap ok because: the parser rule only accepts valid operators
-                        op: op.try_into().unwrap(),
-                       value: v.into(),
-                    })
-                }
+            {
+                // unwrap ok because: the parser rule only accepts valid operators
+                Specifier::new_unchecked(op.try_into().unwrap(), v.into())
             }
 
         rule version_many() -> Specifiers
@@ -42,8 +34,14 @@ peg::parser! {
         pub rule versionspec() -> Specifiers
             = ("(" vm:version_many() ")" { vm }) / version_many()
 
-        rule urlspec() -> Requirement
-            = "@" {? Err("direct url references not currently supported") }
+        // PEP 508 includes a whole URL grammar here, but like 'packaging' we just take
+        // everything up to the next whitespace and leave further validation to
+        // whoever uses the URL.
+        rule uri() -> &'input str
+            = quiet!{ $([^ ' ' | '\t' | '\r' | '\n']+) } / expected!("URL")
+
+        rule urlspec() -> String
+            = "@" _ u:uri() { u.to_owned() }
 
         rule not_in() -> &'static str
             = "not" wsp()+ "in" { "not in" }
@@ -73,6 +71,9 @@ peg::parser! {
             = s:(python_squote_str() / python_dquote_str())
               { marker::Value::Literal(s.into()) }
 
+        rule ident_continue()
+            = ['A'..='Z' | 'a'..='z' | '0'..='9' | '_' | '.']
+
         rule env_var(parse_extra: ParseExtra) -> marker::Value
             = var:$(
                 "python_version" / "python_full_version" / "os_name"
@@ -80,7 +81,7 @@ peg::parser! {
                 / "platform_version" / "platform_machine"
                 / "platform_python_implementation" / "implementation_name"
                 / "implementation_version" / "extra"
-              )
+              ) !ident_continue()
               {?
                if ParseExtra::NotAllowed == parse_extra && var == "extra" {
                    return Err("'extra' marker is not valid in this context")
@@ -93,20 +94,33 @@ peg::parser! {
            = var:$(
                "os.name" / "sys.platform" / "platform.version" / "platform.machine"
                / "platform.python_implementation"
-             )
+             ) !ident_continue()
              {
                marker::Value::Variable(var.replace('.', "_"))
              }
 
         rule setuptools_env_var() -> marker::Value
-           = "python_implementation"
+           = "python_implementation" !ident_continue()
              {
                marker::Value::Variable("platform_python_implementation".into())
              }
 
+        // Anything else that looks like a variable. We accept these here so that we
+        // can give a better error than "expected one of <long list>" (see
+        // EnvMarkerExpr::check_variables), and for callers that allow extension
+        // variables.
+        rule unknown_env_var() -> marker::Value
+            = var:$(quiet!{ ['A'..='Z' | 'a'..='z' | '_'] ident_continue()* })
+              {?
+               if var == "extra" {
+                   return Err("'extra' marker is not valid in this context")
+               }
+               Ok(marker::Value::Variable(var.to_owned()))
+              }
+
         rule marker_value(parse_extra: ParseExtra) -> marker::Value
             = _ v:(env_var(parse_extra) / pep345_env_var() / setuptools_env_var()
-                   / python_str())
+                   / python_str() / unknown_env_var())
               { v }
 
         rule marker_expr(parse_extra: ParseExtra) -> marker::EnvMarkerExpr
@@ -124,6 +138,7 @@ peg::parser! {
                       ">=" => Operator { op: Compare(GreaterThanEqual), lhs, rhs },
                       ">" => Operator { op: Compare(StrictlyGreaterThan), lhs, rhs },
                       "~=" => Operator { op: Compare(Compatible), lhs, rhs },
+                      "===" => Operator { op: Compare(ArbitraryEqual), lhs, rhs },
                       "in" => Operator { op: In, lhs, rhs },
                       "not in" => Operator { op: NotIn, lhs, rhs },
                       _ => panic!("op can't be {:?}!", op),
@@ -161,28 +176,45 @@ peg::parser! {
         rule name_req(parse_extra: ParseExtra) -> Requirement
             = name:name()
               _ extras:(extras() / "" { Vec::new() })
-              _ specifiers:(versionspec() / "" { Specifiers(Vec::new()) })
+              _ specifiers:(versionspec() / "" { Specifiers::empty() })
               _ env_marker_expr:(quoted_marker(parse_extra)?)
               {
                   Requirement {
                       name,
                       extras,
                       specifiers,
+                      url: None,
                       env_marker_expr,
                   }
               }
 
+        // The marker has to be separated from the URL by whitespace, since otherwise
+        // the ';' would be part of the URL.
         rule url_req(parse_extra: ParseExtra) -> Requirement
             = name:name()
               _ extras:(extras() / "" { Vec::new() })
               _ url:urlspec()
-              _ env_marker:((wsp() q:quoted_marker(parse_extra) { q })?)
+              env_marker_expr:((wsp()+ q:quoted_marker(parse_extra) { q })?)
             {
-                // because urlspec() errors out unconditionally, up above
-                unreachable!()
+                Requirement {
+                    name,
+                    extras,
+                    specifiers: Specifiers::empty(),
+                    url: Some(url),
+                    env_marker_expr,
+                }
             }
 
+        // Not valid PEP 508, but an easy mistake to make, so give a better error than
+        // "expected end of input"
+        rule specifiers_and_url() -> Requirement
+            = name() _ extras()? _ versionspec() _ "@"
+              {? Err("a direct URL reference can't also have version specifiers") }
+
+        // specifiers_and_url has to come before name_req, or else name_req will match
+        // the part before the '@' and then the whole parse fails
         pub rule requirement(parse_extra: ParseExtra) -> Requirement
-            = _ r:( url_req(parse_extra) / name_req(parse_extra) ) _ { r }
+            = _ r:( url_req(parse_extra) / specifiers_and_url() / name_req(parse_extra) ) _
+              { r }
     }
 }
@@ -6,41 +6,29 @@ use crate::prelude::*;
 //  === "some string"
 //  @ some_url
 //
-// Not sure if we should bother supporting them. For === they're easy to parse
-// and represent (same as all the other binary comparisons), but I don't know
-// what the semantics is, b/c we fully parse all versions. PEP 440 says "The
-// primary use case ... is to allow for specifying a version which cannot
-// otherwise by represented by this PEP". Maybe if we find ourselves supporting
-// LegacyVersion-type versions, we should add this then? Though even then, I'm not sure
-// we can convince pubgrub to handle it.
+// For === they're easy to parse and represent (same as all the other binary
+// comparisons). PEP 440 says "The primary use case ... is to allow for specifying a
+// version which cannot otherwise by represented by this PEP", but since we fully parse
+// all versions, all we can do is compare the normalized version string against the
+// raw right-hand side. That works in Specifier::satisfied_by, but there's no range
+// representation for it, so pubgrub can't handle it.
 //
-// If we do want to parse @ syntax, the problem is more: how do we represent
-// them? Because it *replaces* version constraints, so I guess inside the
-// Requirement object we'd need something like:
+// @ *replaces* the version constraints, so a Requirement has either specifiers or a
+// url, never both (the parser enforces this). PEP 508 includes an entire copy of (some
+// version of) the standard URL syntax. We don't want to do that, both because it's
+// wildly more complicated than required, and because there are >3 different standards
+// purpoting to define URL syntax and we don't want to take sides. So we do what the
+// 'packaging' module does:
 //
-//   enum Specifiers {
-//      Direct(Url),
-//      Index(Vec<Specifier>),
-//   }
+//    URI = Regex(r"[^ ]+")("url")
 //
-// ? But then that complexity propagates through to everything that uses
-// Requirements.
+// and keep it as a plain string.
 //
 // Also, I don't think @ is allowed in public indexes like PyPI?
 //
-// NB: if we do decide to handle '@', then PEP 508 includes an entire copy of
-// (some version of) the standard URL syntax. We don't want to do that, both
-// because it's wildly more complicated than required, and because there are
-// >3 different standards purpoting to define URL syntax and we don't want to
-// take sides. But! The 'packaging' module just does
-//
-//    URI = Regex(r"[^ ]+")("url")
-//
-// ...so we can just steal some version of that.
-//
 // For resolving, we can treat it as a magic package that provides/depends on the
 // version it declares, so it can satisfy other dependencies that use the name or
-// versions.
+// versions. But the resolver doesn't do that yet, so it rejects them.
 
 pub mod marker {
     use std::{borrow::Borrow, hash::Hash};
@@ -67,6 +55,33 @@ pub mod marker {
         Operator { op: Op, lhs: Value, rhs: Value },
     }
 
+    /// Every variable name PEP 508 defines, plus 'extra'.
+    pub const PEP508_VARIABLES: &[&str] = &[
+        "python_version",
+        "python_full_version",
+        "os_name",
+        "sys_platform",
+        "platform_release",
+        "platform_system",
+        "platform_version",
+        "platform_machine",
+        "platform_python_implementation",
+        "implementation_name",
+        "implementation_version",
+        "extra",
+    ];
+
+    /// Which variables a marker expression may use.
+    #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+    pub enum VariableStrictness {
+        /// Only the ones PEP 508 defines (see `PEP508_VARIABLES`).
+        Strict,
+        /// Anything that looks like an identifier, for tools that define extra
+        /// variables of their own. Evaluating such a marker needs an `Env` that knows
+        /// about them.
+        AllowUnknown,
+    }
+
     pub trait Env {
         fn get_marker_var(&self, var: &str) -> Option<&str>;
     }
@@ -77,6 +92,276 @@ pub mod marker {
         }
     }
 
+    /// The PEP 508 environment marker variables describing some target Python
+    /// environment. (`extra` isn't here; it depends on how a package was requested,
+    /// not on the environment. See `EnvMarkerExpr::evaluate`.)
+    ///
+    /// Deserializing checks the version-valued variables; see `validate`.
+    #[derive(Debug, Clone, PartialEq, Eq, Default)]
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    #[cfg_attr(feature = "serde", serde(try_from = "HashMap<String, String>"))]
+    pub struct MarkerEnvironment {
+        pub implementation_name: String,
+        pub implementation_version: String,
+        pub os_name: String,
+        pub platform_machine: String,
+        pub platform_python_implementation: String,
+        pub platform_release: String,
+        pub platform_system: String,
+        pub platform_version: String,
+        pub python_full_version: String,
+        pub python_version: String,
+        pub sys_platform: String,
+    }
+
+    impl MarkerEnvironment {
+        /// Starts a MarkerEnvironment for the given Python version, filling in
+        /// python_full_version and the python_version derived from it (e.g. "3.11.4"
+        /// gives "3.11"), so the two can't disagree. The other variables are left
+        /// empty; set them with struct update syntax:
+        ///
+        ///   MarkerEnvironment { sys_platform: "linux".into(), ..for_python("3.11.4")? }
+        pub fn for_python(python_full_version: &str) -> Result<MarkerEnvironment> {
+            let version: Version =
+                python_full_version.try_into().wrap_err_with(|| {
+                    format!("invalid python_full_version {:?}", python_full_version)
+                })?;
+            let release = version.release_segments();
+            let major = release[0];
+            let minor = release.get(1).copied().unwrap_or(0);
+            Ok(MarkerEnvironment {
+                python_full_version: python_full_version.to_owned(),
+                python_version: format!("{major}.{minor}"),
+                ..Default::default()
+            })
+        }
+    }
+
+    impl MarkerEnvironment {
+        /// Builds a MarkerEnvironment out of some other environment, e.g. the
+        /// variables from a pybi's metadata. Every variable has to be present (extra
+        /// ones are ignored), and the result has to pass `validate`.
+        pub fn from_vars(vars: &dyn Env) -> Result<MarkerEnvironment> {
+            let mut missing = Vec::new();
+            let mut get = |name: &str| match vars.get_marker_var(name) {
+                Some(value) => value.to_owned(),
+                None => {
+                    missing.push(name.to_owned());
+                    String::new()
+                }
+            };
+            let env = MarkerEnvironment {
+                implementation_name: get("implementation_name"),
+                implementation_version: get("implementation_version"),
+                os_name: get("os_name"),
+                platform_machine: get("platform_machine"),
+                platform_python_implementation: get("platform_python_implementation"),
+                platform_release: get("platform_release"),
+                platform_system: get("platform_system"),
+                platform_version: get("platform_version"),
+                python_full_version: get("python_full_version"),
+                python_version: get("python_version"),
+                sys_platform: get("sys_platform"),
+            };
+            if !missing.is_empty() {
+                bail!("missing marker variables: {}", missing.join(", "));
+            }
+            env.validate()?;
+            Ok(env)
+        }
+
+        /// Checks the variables that markers compare as versions, so that mistakes
+        /// show up here instead of as confusing results from `evaluate`:
+        ///
+        /// - python_full_version has to be a valid version
+        /// - python_version has to be its "major.minor" prefix
+        /// - implementation_version has to be a valid version, if it's set (it can be
+        ///   left empty when it's unknown)
+        ///
+        /// The error lists every variable with a problem.
+        pub fn validate(&self) -> Result<()> {
+            let mut problems = Vec::new();
+            match Version::try_from(self.python_full_version.as_str()) {
+                Ok(full) => {
+                    let release = full.release_segments();
+                    let expected = format!(
+                        "{}.{}",
+                        release[0],
+                        release.get(1).copied().unwrap_or(0)
+                    );
+                    if self.python_version != expected {
+                        problems.push(format!(
+                            "python_version {:?} doesn't match python_full_version \
+                             {:?} (expected {:?})",
+                            self.python_version, self.python_full_version, expected
+                        ));
+                    }
+                }
+                Err(_) => problems.push(format!(
+                    "python_full_version {:?} isn't a valid version",
+                    self.python_full_version
+                )),
+            }
+            if !self.implementation_version.is_empty()
+                && Version::try_from(self.implementation_version.as_str()).is_err()
+            {
+                problems.push(format!(
+                    "implementation_version {:?} isn't a valid version",
+                    self.implementation_version
+                ));
+            }
+            if !problems.is_empty() {
+                bail!("invalid marker environment: {}", problems.join("; "));
+            }
+            Ok(())
+        }
+    }
+
+    impl TryFrom<HashMap<String, String>> for MarkerEnvironment {
+        type Error = eyre::Report;
+
+        fn try_from(vars: HashMap<String, String>) -> Result<Self, Self::Error> {
+            MarkerEnvironment::from_vars(&vars)
+        }
+    }
+
+    impl Env for MarkerEnvironment {
+        fn get_marker_var(&self, var: &str) -> Option<&str> {
+            Some(match var {
+                "implementation_name" => &self.implementation_name,
+                "implementation_version" => &self.implementation_version,
+                "os_name" => &self.os_name,
+                "platform_machine" => &self.platform_machine,
+                "platform_python_implementation" => {
+                    &self.platform_python_implementation
+                }
+                "platform_release" => &self.platform_release,
+                "platform_system" => &self.platform_system,
+                "platform_version" => &self.platform_version,
+                "python_full_version" => &self.python_full_version,
+                "python_version" => &self.python_version,
+                "sys_platform" => &self.sys_platform,
+                _ => return None,
+            })
+        }
+    }
+
+    // Layers a value for 'extra' on top of some other environment.
+    struct WithExtra<'a> {
+        env: &'a dyn Env,
+        extra: &'a str,
+    }
+
+    impl<'a> Env for WithExtra<'a> {
+        fn get_marker_var(&self, var: &str) -> Option<&str> {
+            if var == "extra" {
+                Some(self.extra)
+            } else {
+                self.env.get_marker_var(var)
+            }
+        }
+    }
+
+    // An environment that only knows about 'extra', for simplify_out_extra.
+    struct ExtraEnv<'a> {
+        extra: Option<&'a str>,
+    }
+
+    impl<'a> Env for ExtraEnv<'a> {
+        fn get_marker_var(&self, var: &str) -> Option<&str> {
+            if var == "extra" {
+                self.extra.or(Some(""))
+            } else {
+                None
+            }
+        }
+    }
+
+    /// An environment where only some of the marker variables are known, for
+    /// `EnvMarkerExpr::simplify`. Keys are marker variable names, like
+    /// "sys_platform".
+    #[derive(Debug, Clone, Default, PartialEq, Eq)]
+    pub struct PartialMarkerEnvironment {
+        pub vars: HashMap<String, String>,
+    }
+
+    impl Env for PartialMarkerEnvironment {
+        fn get_marker_var(&self, var: &str) -> Option<&str> {
+            self.vars.get(var).map(|s| s.as_str())
+        }
+    }
+
+    /// The result of partially evaluating a marker expression.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum Simplified {
+        True,
+        False,
+        Expr(EnvMarkerExpr),
+    }
+
+    impl Display for Simplified {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self {
+                Simplified::True => write!(f, "true"),
+                Simplified::False => write!(f, "false"),
+                Simplified::Expr(e) => write!(f, "{}", e),
+            }
+        }
+    }
+
+    impl Simplified {
+        pub fn eval(&self, env: &dyn marker::Env) -> Result<bool> {
+            match self {
+                Simplified::True => Ok(true),
+                Simplified::False => Ok(false),
+                Simplified::Expr(expr) => expr.eval(env),
+            }
+        }
+
+        pub fn and(self, other: Simplified) -> Simplified {
+            match (self, other) {
+                (Simplified::True, Simplified::True) => Simplified::True,
+                (_, Simplified::False) => Simplified::False,
+                (Simplified::False, _) => Simplified::False,
+                (Simplified::Expr(lhs), Simplified::True) => Simplified::Expr(lhs),
+                (Simplified::True, Simplified::Expr(rhs)) => Simplified::Expr(rhs),
+                (Simplified::Expr(lhs), Simplified::Expr(rhs)) => {
+                    Simplified::Expr(EnvMarkerExpr::And(Box::new(lhs), Box::new(rhs)))
+                }
+            }
+        }
+
+        pub fn or(self, other: Simplified) -> Simplified {
+            match (self, other) {
+                (Simplified::False, Simplified::False) => Simplified::False,
+                (_, Simplified::True) => Simplified::True,
+                (Simplified::True, _) => Simplified::True,
+                (Simplified::Expr(lhs), Simplified::False) => Simplified::Expr(lhs),
+                (Simplified::False, Simplified::Expr(rhs)) => Simplified::Expr(rhs),
+                (Simplified::Expr(lhs), Simplified::Expr(rhs)) => {
+                    Simplified::Expr(EnvMarkerExpr::Or(Box::new(lhs), Box::new(rhs)))
+                }
+            }
+        }
+    }
+
+    /// Evaluates every comparison involving `extra` (`None` meaning the package was
+    /// requested without extras), and simplifies the expression around the results.
+    /// Whatever's left doesn't mention `extra`; if that's not possible (e.g. for
+    /// `extra == python_version`), it's an error.
+    pub fn simplify_out_extra(
+        expr: &EnvMarkerExpr,
+        extra: Option<&str>,
+    ) -> Result<Simplified> {
+        let simplified = expr.simplify(&ExtraEnv { extra });
+        if let Simplified::Expr(remaining) = &simplified {
+            if let Some(anomalous) = remaining.find_extra() {
+                bail!("anomalous 'extra' expression: {}", anomalous);
+            }
+        }
+        Ok(simplified)
+    }
+
     impl Value {
         pub fn eval<'a>(&'a self, env: &'a dyn Env) -> Result<&'a str> {
             match self {
@@ -96,6 +381,18 @@ pub mod marker {
                 Value::Literal(_) => false,
             }
         }
+
+        /// Whether this is one of the variables that always holds a version, so
+        /// comparisons against it have to use PEP 440 rules.
+        pub fn is_version_valued(&self) -> bool {
+            match self {
+                Value::Variable(varname) => matches!(
+                    varname.as_str(),
+                    "python_version" | "python_full_version" | "implementation_version"
+                ),
+                Value::Literal(_) => false,
+            }
+        }
     }
 
     impl Display for Value {
@@ -114,6 +411,98 @@ pub mod marker {
     }
 
     impl EnvMarkerExpr {
+        /// Evaluates this marker for a package that was requested with the given
+        /// extras. A marker that mentions `extra` is true if it's true for any one of
+        /// them (like pip, we check each extra separately); with no extras, `extra`
+        /// is the empty string.
+        pub fn evaluate(
+            &self,
+            env: &MarkerEnvironment,
+            extras: &[Extra],
+        ) -> Result<bool> {
+            if extras.is_empty() {
+                return self.eval(&WithExtra { env, extra: "" });
+            }
+            for extra in extras {
+                let extra = extra.normalized();
+                if self.eval(&WithExtra { env, extra })? {
+                    return Ok(true);
+                }
+            }
+            Ok(false)
+        }
+
+        /// Evaluates whatever parts of the expression `env` has enough information
+        /// for, and simplifies the rest around them: "A and B" drops A if it's
+        /// known to be true, or becomes false if A is known to be false, etc.
+        /// Comparisons that can't be evaluated -- usually because `env` doesn't have
+        /// one of the variables -- are left as they are.
+        pub fn simplify(&self, env: &dyn Env) -> Simplified {
+            match self {
+                EnvMarkerExpr::And(lhs, rhs) => {
+                    lhs.simplify(env).and(rhs.simplify(env))
+                }
+                EnvMarkerExpr::Or(lhs, rhs) => lhs.simplify(env).or(rhs.simplify(env)),
+                EnvMarkerExpr::Operator { .. } => match self.eval(env) {
+                    Ok(true) => Simplified::True,
+                    Ok(false) => Simplified::False,
+                    Err(_) => Simplified::Expr(self.clone()),
+                },
+            }
+        }
+
+        /// All the variable names the expression refers to, including 'extra'. Legacy
+        /// spellings like "os.name" come back in their PEP 508 form.
+        pub fn variables(&self) -> std::collections::BTreeSet<String> {
+            let mut vars = std::collections::BTreeSet::new();
+            self.collect_variables(&mut vars);
+            vars
+        }
+
+        /// Errors out if the expression uses any variables that PEP 508 doesn't define,
+        /// naming them. These are almost always typos, like "python_verison", which
+        /// would otherwise only show up when we try to evaluate the marker.
+        pub fn check_variables(&self) -> Result<()> {
+            let unknown: Vec<String> = self
+                .variables()
+                .into_iter()
+                .filter(|var| !PEP508_VARIABLES.contains(&var.as_str()))
+                .map(|var| format!("{:?}", var))
+                .collect();
+            if !unknown.is_empty() {
+                bail!("unknown environment marker variable {}", unknown.join(", "));
+            }
+            Ok(())
+        }
+
+        fn collect_variables(&self, vars: &mut std::collections::BTreeSet<String>) {
+            match self {
+                EnvMarkerExpr::And(lhs, rhs) | EnvMarkerExpr::Or(lhs, rhs) => {
+                    lhs.collect_variables(vars);
+                    rhs.collect_variables(vars);
+                }
+                EnvMarkerExpr::Operator { lhs, rhs, .. } => {
+                    for value in [lhs, rhs] {
+                        if let Value::Variable(var) = value {
+                            vars.insert(var.clone());
+                        }
+                    }
+                }
+            }
+        }
+
+        // Returns the first comparison that involves 'extra', if any.
+        fn find_extra(&self) -> Option<&EnvMarkerExpr> {
+            match self {
+                EnvMarkerExpr::And(lhs, rhs) | EnvMarkerExpr::Or(lhs, rhs) => {
+                    lhs.find_extra().or_else(|| rhs.find_extra())
+                }
+                EnvMarkerExpr::Operator { lhs, rhs, .. } => {
+                    (lhs.is_extra() || rhs.is_extra()).then_some(self)
+                }
+            }
+        }
+
         pub fn eval(&self, env: &dyn Env) -> Result<bool> {
             Ok(match self {
                 EnvMarkerExpr::And(lhs, rhs) => lhs.eval(env)? && rhs.eval(env)?,
@@ -140,10 +529,27 @@ pub mod marker {
                     match op {
                         Op::In => rhs_val.contains(lhs_val),
                         Op::NotIn => !rhs_val.contains(lhs_val),
+                        Op::Compare(op)
+                            if *op != CompareOp::ArbitraryEqual
+                                && (lhs.is_version_valued()
+                                    || rhs.is_version_valued()) =>
+                        {
+                            // These always hold versions, so falling back to string
+                            // comparison would silently give nonsense ("3.10" < "3.9").
+                            // If either side isn't a valid version, that's an error.
+                            let lhs_ver: Version = lhs_val
+                                .parse()
+                                .wrap_err_with(|| format!("in marker {}", self))?;
+                            let rhs_ranges = op
+                                .to_ranges(rhs_val)
+                                .wrap_err_with(|| format!("in marker {}", self))?;
+                            rhs_ranges.into_iter().any(|r| r.contains(&lhs_ver))
+                        }
                         Op::Compare(op) => {
                             // If both sides can be parsed as versions (or the RHS can
                             // be parsed as a wildcard with a wildcard-accepting op),
-                            // then we do a version comparison
+                            // then PEP 508 says we do a version comparison, even for
+                            // variables like platform_release.
                             if let Ok(lhs_ver) = lhs_val.parse() {
                                 if let Ok(rhs_ranges) = op.to_ranges(rhs_val) {
                                     return Ok(rhs_ranges
@@ -159,6 +565,7 @@ pub mod marker {
                                 Equal => lhs_val == rhs_val,
                                 GreaterThanEqual => lhs_val >= rhs_val,
                                 StrictlyGreaterThan => lhs_val > rhs_val,
+                                ArbitraryEqual => lhs_val == rhs_val,
                                 Compatible => {
                                     bail!("~= requires valid version strings")
                                 }
@@ -172,9 +579,22 @@ pub mod marker {
 
     impl Display for EnvMarkerExpr {
         fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            // 'and' binds tighter than 'or', so the only place we need parentheses
+            // is around an 'or' that's inside an 'and'.
+            let and_operand = |f: &mut std::fmt::Formatter<'_>, e: &EnvMarkerExpr| {
+                if let EnvMarkerExpr::Or(..) = e {
+                    write!(f, "({})", e)
+                } else {
+                    write!(f, "{}", e)
+                }
+            };
             match self {
-                EnvMarkerExpr::And(lhs, rhs) => write!(f, "({} and {})", lhs, rhs)?,
-                EnvMarkerExpr::Or(lhs, rhs) => write!(f, "({} or {})", lhs, rhs)?,
+                EnvMarkerExpr::And(lhs, rhs) => {
+                    and_operand(f, lhs)?;
+                    write!(f, " and ")?;
+                    and_operand(f, rhs)?;
+                }
+                EnvMarkerExpr::Or(lhs, rhs) => write!(f, "{} or {}", lhs, rhs)?,
                 EnvMarkerExpr::Operator { op, lhs, rhs } => write!(
                     f,
                     "{} {} {}",
@@ -192,7 +612,8 @@ pub mod marker {
     }
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Hash, SerializeDisplay, DeserializeFromStr)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(SerializeDisplay, DeserializeFromStr))]
 pub struct StandaloneMarkerExpr(pub marker::EnvMarkerExpr);
 
 impl Display for StandaloneMarkerExpr {
@@ -205,9 +626,25 @@ impl TryFrom<&str> for StandaloneMarkerExpr {
     type Error = eyre::Report;
 
     fn try_from(value: &str) -> Result<Self, Self::Error> {
-        let expr = super::reqparse::marker(value, ParseExtra::NotAllowed)
-            .wrap_err_with(|| {
-                format!("Failed parsing env marker expression {:?}", value)
+        StandaloneMarkerExpr::parse(value, marker::VariableStrictness::Strict)
+    }
+}
+
+impl StandaloneMarkerExpr {
+    pub fn parse(
+        input: &str,
+        strictness: marker::VariableStrictness,
+    ) -> Result<StandaloneMarkerExpr> {
+        let expr = super::reqparse::marker(input, ParseExtra::NotAllowed)
+            .map_err(eyre::Report::from)
+            .and_then(|expr| {
+                if strictness == marker::VariableStrictness::Strict {
+                    expr.check_variables()?;
+                }
+                Ok(expr)
+            })
+            .wrap_err_with(|| VocabError::InvalidMarker {
+                input: input.into(),
             })?;
         Ok(StandaloneMarkerExpr(expr))
     }
@@ -215,28 +652,289 @@ impl TryFrom<&str> for StandaloneMarkerExpr {
 
 try_from_str_boilerplate!(StandaloneMarkerExpr);
 
+/// Whether the `extra` variable is allowed in env markers. This is about markers like
+/// `; extra == "test"`, not `foo[test]` -- those are always allowed syntactically.
+///
+/// `extra` only makes sense in a package's own Requires-Dist metadata, where it says
+/// which of the package's extras pulls in the requirement. Anywhere else (user
+/// requests, standalone markers) there's nothing for it to refer to, so it's an error.
+///
+/// - `PackageRequirement` (and deserializing a bare `Requirement`) use `Allowed`
+/// - `UserRequirement`, `PythonRequirement`, and `StandaloneMarkerExpr` use
+///   `NotAllowed`. `PythonRequirement` additionally rejects `[extras]` and markers.
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum ParseExtra {
     Allowed,
     NotAllowed,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// Equality (and hashing) ignore the order of extras and specifiers, and duplicate
+/// extras, so e.g. "foo[a,b] >=1,<2" == "foo[b,a] <2,>=1". Names, extras, and markers
+/// already compare in normalized form.
+///
+/// The key is built from the specifiers' strings, never their caches, and a
+/// Requirement can't be changed through a shared reference, so it's safe to use as a
+/// HashMap/HashSet key. (That's why clippy.toml tells `mutable_key_type` not to worry
+/// about the OnceCells inside Specifier.)
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(SerializeDisplay))]
 pub struct Requirement {
     pub name: PackageName,
     pub extras: Vec<Extra>,
     pub specifiers: Specifiers,
+    // A PEP 508 direct reference ("name @ url"). If this is set, specifiers is empty.
+    pub url: Option<String>,
     pub env_marker_expr: Option<marker::EnvMarkerExpr>,
 }
 
+/// Builds a `Requirement` piece by piece, for code that generates requirements and
+/// doesn't want to go through formatting and parsing a string. Get one from
+/// `Requirement::builder`.
+#[derive(Debug, Clone)]
+pub struct RequirementBuilder {
+    name: PackageName,
+    extras: Vec<Extra>,
+    specifiers: Vec<Specifier>,
+    url: Option<String>,
+    env_marker_expr: Option<marker::EnvMarkerExpr>,
+    variables: marker::VariableStrictness,
+}
+
+impl RequirementBuilder {
+    pub fn extra(mut self, extra: Extra) -> Self {
+        self.extras.push(extra);
+        self
+    }
+
+    pub fn specifier(mut self, specifier: Specifier) -> Self {
+        self.specifiers.push(specifier);
+        self
+    }
+
+    pub fn specifiers(mut self, specifiers: &Specifiers) -> Self {
+        self.specifiers.extend(specifiers.iter().cloned());
+        self
+    }
+
+    pub fn url(mut self, url: &str) -> Self {
+        self.url = Some(url.into());
+        self
+    }
+
+    /// Sets the marker, replacing any earlier one.
+    pub fn marker(mut self, expr: marker::EnvMarkerExpr) -> Self {
+        self.env_marker_expr = Some(expr);
+        self
+    }
+
+    /// Which variables the marker may use; `Strict` unless you say otherwise.
+    pub fn variables(mut self, variables: marker::VariableStrictness) -> Self {
+        self.variables = variables;
+        self
+    }
+
+    /// Checks for the same things the parser would: a requirement can't have both a
+    /// URL and specifiers, the URL can't contain whitespace, and the marker can only
+    /// use the variables allowed by `variables`.
+    pub fn build(self) -> Result<Requirement> {
+        if let Some(url) = &self.url {
+            if !self.specifiers.is_empty() {
+                bail!("a direct URL reference can't also have version specifiers");
+            }
+            if url.is_empty() || url.contains(char::is_whitespace) {
+                bail!("invalid URL {:?}", url);
+            }
+        }
+        if let Some(expr) = &self.env_marker_expr {
+            if self.variables == marker::VariableStrictness::Strict {
+                expr.check_variables()?;
+            }
+        }
+        Ok(Requirement {
+            name: self.name,
+            extras: self.extras,
+            specifiers: Specifiers(self.specifiers),
+            url: self.url,
+            env_marker_expr: self.env_marker_expr,
+        })
+    }
+}
+
 impl Requirement {
+    pub fn builder(name: PackageName) -> RequirementBuilder {
+        RequirementBuilder {
+            name,
+            extras: Vec::new(),
+            specifiers: Vec::new(),
+            url: None,
+            env_marker_expr: None,
+            variables: marker::VariableStrictness::Strict,
+        }
+    }
+
+    pub fn name(&self) -> &PackageName {
+        &self.name
+    }
+
+    pub fn extras(&self) -> &[Extra] {
+        &self.extras
+    }
+
+    pub fn specifiers(&self) -> &Specifiers {
+        &self.specifiers
+    }
+
+    pub fn marker(&self) -> Option<&marker::EnvMarkerExpr> {
+        self.env_marker_expr.as_ref()
+    }
+
+    pub fn url(&self) -> Option<&str> {
+        self.url.as_deref()
+    }
+
+    /// The URL with its %-escapes decoded, e.g. "file:///tmp/my%20dir/foo.whl" gives
+    /// "file:///tmp/my dir/foo.whl". `url()` keeps the URL exactly as written, and this
+    /// always decodes from that, so something like "%2520" decodes once to "%20". Errors
+    /// if the decoded bytes aren't UTF-8.
+    pub fn decoded_url(&self) -> Result<Option<String>> {
+        self.url
+            .as_deref()
+            .map(|url| {
+                Ok(percent_encoding::percent_decode_str(url)
+                    .decode_utf8()
+                    .wrap_err_with(|| format!("invalid %-escapes in URL {:?}", url))?
+                    .into_owned())
+            })
+            .transpose()
+    }
+
+    /// Parses a requirement whose marker may only use the PEP 508 variables.
     pub fn parse(input: &str, parse_extra: ParseExtra) -> Result<Requirement> {
-        let req =
-            super::reqparse::requirement(input, parse_extra).wrap_err_with(|| {
-                format!("Failed parsing requirement string {:?})", input)
+        Requirement::parse_with(input, parse_extra, marker::VariableStrictness::Strict)
+    }
+
+    /// Like `parse`, but lets you choose which marker variables are allowed.
+    pub fn parse_with(
+        input: &str,
+        parse_extra: ParseExtra,
+        variables: marker::VariableStrictness,
+    ) -> Result<Requirement> {
+        let req = super::reqparse::requirement(input, parse_extra)
+            .map_err(eyre::Report::from)
+            .and_then(|req| {
+                if let Some(expr) = &req.env_marker_expr {
+                    if variables == marker::VariableStrictness::Strict {
+                        expr.check_variables()?;
+                    }
+                }
+                Ok(req)
+            })
+            .wrap_err_with(|| {
+                format!("Failed parsing requirement string {:?}", input)
             })?;
         Ok(req)
     }
+
+    /// Substitutes the given active extras into this requirement's marker. Returns
+    /// None if the extras rule the requirement out, and otherwise the requirement with
+    /// the 'extra' comparisons simplified away (dropping the marker entirely if
+    /// nothing else is left). Like `applies`, each extra is checked separately.
+    pub fn with_extras(&self, extras: &[Extra]) -> Result<Option<Requirement>> {
+        let Some(expr) = &self.env_marker_expr else {
+            return Ok(Some(self.clone()));
+        };
+        let mut simplified = marker::Simplified::False;
+        if extras.is_empty() {
+            simplified = marker::simplify_out_extra(expr, None)?;
+        }
+        for extra in extras {
+            simplified = simplified
+                .or(marker::simplify_out_extra(expr, Some(extra.normalized()))?);
+        }
+        Ok(match simplified {
+            marker::Simplified::False => None,
+            marker::Simplified::True => Some(Requirement {
+                env_marker_expr: None,
+                ..self.clone()
+            }),
+            marker::Simplified::Expr(expr) => Some(Requirement {
+                env_marker_expr: Some(expr),
+                ..self.clone()
+            }),
+        })
+    }
+
+    /// Whether this requirement applies in the given environment, for a package that
+    /// was requested with the given extras. True if there's no marker at all.
+    pub fn applies(
+        &self,
+        env: &marker::MarkerEnvironment,
+        active_extras: &[Extra],
+    ) -> Result<bool> {
+        match &self.env_marker_expr {
+            None => Ok(true),
+            Some(expr) => expr.evaluate(env, active_extras),
+        }
+    }
+}
+
+// A bare Requirement doesn't know which context it came from, so deserialize it in
+// the most permissive mode. Use the wrapper types below to be stricter.
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for Requirement {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        Requirement::parse_with(
+            &s,
+            ParseExtra::Allowed,
+            marker::VariableStrictness::AllowUnknown,
+        )
+        .map_err(|e| serde::de::Error::custom(format!("{:#}", e)))
+    }
+}
+
+impl Requirement {
+    fn normalized_key(
+        &self,
+    ) -> (
+        &PackageName,
+        Vec<&str>,
+        Vec<String>,
+        &Option<String>,
+        &Option<marker::EnvMarkerExpr>,
+    ) {
+        let mut extras: Vec<&str> =
+            self.extras.iter().map(|e| e.normalized()).collect();
+        extras.sort_unstable();
+        extras.dedup();
+        let mut specifiers: Vec<String> =
+            self.specifiers.iter().map(|s| s.to_string()).collect();
+        specifiers.sort_unstable();
+        (
+            &self.name,
+            extras,
+            specifiers,
+            &self.url,
+            &self.env_marker_expr,
+        )
+    }
+}
+
+impl PartialEq for Requirement {
+    fn eq(&self, other: &Self) -> bool {
+        self.normalized_key() == other.normalized_key()
+    }
+}
+
+impl Eq for Requirement {}
+
+impl std::hash::Hash for Requirement {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.normalized_key().hash(state);
+    }
 }
 
 impl Display for Requirement {
@@ -254,21 +952,34 @@ impl Display for Requirement {
             }
             write!(f, "]")?;
         }
-        if !self.specifiers.0.is_empty() {
+        if !self.specifiers.is_empty() {
             write!(f, " {}", self.specifiers)?;
         }
+        if let Some(url) = &self.url {
+            write!(f, " @ {}", url)?;
+        }
         if let Some(env_marker) = &self.env_marker_expr {
-            write!(f, "; {}", env_marker)?;
+            // after a url, the ; has to be separated by whitespace
+            if self.url.is_some() {
+                write!(f, " ; {}", env_marker)?;
+            } else {
+                write!(f, "; {}", env_marker)?;
+            }
         }
         Ok(())
     }
 }
 
-#[derive(
-    Shrinkwrap, Debug, Clone, PartialEq, Eq, DeserializeFromStr, SerializeDisplay,
-)]
+#[derive(Shrinkwrap, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(DeserializeFromStr, SerializeDisplay))]
 pub struct PackageRequirement(Requirement);
 
+impl PackageRequirement {
+    pub fn with_extras(&self, extras: &[Extra]) -> Result<Option<PackageRequirement>> {
+        Ok(self.0.with_extras(extras)?.map(PackageRequirement))
+    }
+}
+
 impl Display for PackageRequirement {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         self.0.fmt(f)
@@ -279,18 +990,21 @@ impl TryFrom<&str> for PackageRequirement {
     type Error = eyre::Report;
 
     fn try_from(value: &str) -> Result<Self, Self::Error> {
-        Ok(PackageRequirement(Requirement::parse(
+        // Requires-Dist comes from whatever tool built the package, and some of them
+        // add their own marker variables. We can't evaluate those, but that's no
+        // reason to reject the whole METADATA file.
+        Ok(PackageRequirement(Requirement::parse_with(
             value,
             ParseExtra::Allowed,
+            marker::VariableStrictness::AllowUnknown,
         )?))
     }
 }
 
 try_from_str_boilerplate!(PackageRequirement);
 
-#[derive(
-    Shrinkwrap, Debug, Clone, PartialEq, Eq, DeserializeFromStr, SerializeDisplay,
-)]
+#[derive(Shrinkwrap, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(DeserializeFromStr, SerializeDisplay))]
 pub struct UserRequirement(Requirement);
 
 impl Display for UserRequirement {
@@ -312,9 +1026,8 @@ impl TryFrom<&str> for UserRequirement {
 
 try_from_str_boilerplate!(UserRequirement);
 
-#[derive(
-    Shrinkwrap, Debug, Clone, PartialEq, Eq, DeserializeFromStr, SerializeDisplay,
-)]
+#[derive(Shrinkwrap, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(DeserializeFromStr, SerializeDisplay))]
 pub struct PythonRequirement(Requirement);
 
 impl Display for PythonRequirement {
@@ -356,6 +1069,7 @@ mod test {
     use super::*;
 
     #[test]
+    #[cfg(feature = "serde")]
     fn test_package_requirement_basics() {
         let r: PackageRequirement =
             "twisted[tls] >= 20, != 20.1.*; python_version >= '3' and extra == 'hi'"
@@ -363,11 +1077,12 @@ mod test {
                 .unwrap();
         insta::assert_ron_snapshot!(
             r,
-            @r###""twisted[tls] >= 20, != 20.1.*; (python_version >= \"3\" and extra == \"hi\")""###
+            @r###""twisted[tls] >=20,!=20.1.*; python_version >= \"3\" and extra == \"hi\"""###
         );
     }
 
     #[test]
+    #[cfg(feature = "serde")]
     fn test_user_requirement_basics() {
         assert!(UserRequirement::try_from("twisted; extra == 'hi'").is_err());
         let r: UserRequirement = "twisted[tls] >= 20, != 20.1.*; python_version >= '3'"
@@ -375,11 +1090,12 @@ mod test {
             .unwrap();
         insta::assert_ron_snapshot!(
             r,
-            @r###""twisted[tls] >= 20, != 20.1.*; python_version >= \"3\"""###
+            @r###""twisted[tls] >=20,!=20.1.*; python_version >= \"3\"""###
         );
     }
 
     #[test]
+    #[cfg(feature = "serde")]
     fn test_no_paren_chained_operators() {
         // The formal grammar in PEP 508 fails to parse expressions like:
         //   "_ and _ and _"
@@ -390,18 +1106,19 @@ mod test {
                 .unwrap();
         insta::assert_ron_snapshot!(
             r,
-            @r###""foo; ((os_name == \"a\" and (os_name == \"b\" and os_name == \"c\")) or (os_name == \"d\" or os_name == \"e\"))""###
+            @r###""foo; os_name == \"a\" and os_name == \"b\" and os_name == \"c\" or os_name == \"d\" or os_name == \"e\"""###
         );
     }
 
     #[test]
+    #[cfg(feature = "serde")]
     fn test_legacy_env_marker_vars() {
         // should parse these, and normalize them to their PEP 508 equivalents
         let r: PackageRequirement =
             "foo; os.name == 'nt' and python_implementation == 'pypy'"
                 .try_into()
                 .unwrap();
-        insta::assert_ron_snapshot!(r, @r###""foo; (os_name == \"nt\" and platform_python_implementation == \"pypy\")""###);
+        insta::assert_ron_snapshot!(r, @r###""foo; os_name == \"nt\" and platform_python_implementation == \"pypy\"""###);
     }
 
     #[test]
@@ -413,6 +1130,8 @@ mod test {
             "foo[bar,baz, quux]",
             "foo; python_version >= '3' and sys_platform == \"win32\" or sys_platform != \"linux\"",
             "foo.bar-baz (~=7); 'win' in sys_platform or 'linux' not in sys_platform",
+            "foo[bar] @ https://example.com/foo.whl",
+            "foo @ file:///tmp/foo.tar.gz ; python_version < '3.8'",
         ];
         for req in reqs {
             let ur: UserRequirement = req.try_into().unwrap();
@@ -421,6 +1140,791 @@ mod test {
             let pr: PackageRequirement = req.try_into().unwrap();
             assert_eq!(pr, pr.to_string().try_into().unwrap());
         }
+
+        // extra markers only parse in package requirements
+        for req in [
+            "foo[bar] >= 1; extra == 'test' and python_version < '3.8'",
+            "foo; (extra == 'a' or extra == 'b') and os_name == 'nt'",
+        ] {
+            let pr: PackageRequirement = req.try_into().unwrap();
+            assert_eq!(pr, pr.to_string().try_into().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_invalid_marker_error() {
+        let err = StandaloneMarkerExpr::try_from("os_name = 'nt'").unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<VocabError>(),
+            Some(&VocabError::InvalidMarker {
+                input: "os_name = 'nt'".into()
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_extra_modes() {
+        let with_extra = "foo[bar]; extra == 'test'";
+        assert!(Requirement::parse(with_extra, ParseExtra::Allowed).is_ok());
+        let err = Requirement::parse(with_extra, ParseExtra::NotAllowed).unwrap_err();
+        assert!(format!("{:#}", err).contains("'extra' marker is not valid"));
+
+        // nested inside other expressions too
+        assert!(Requirement::parse(
+            "foo; python_version >= '3' and (os_name == 'nt' or extra == 'x')",
+            ParseExtra::NotAllowed
+        )
+        .is_err());
+
+        assert!(PackageRequirement::try_from(with_extra).is_ok());
+        assert!(UserRequirement::try_from(with_extra).is_err());
+        assert!(StandaloneMarkerExpr::try_from("extra == 'test'").is_err());
+        // [extras] themselves are fine for users...
+        assert!(UserRequirement::try_from("foo[bar]").is_ok());
+        // ...but not for python
+        assert!(PythonRequirement::try_from("cpython[bar] >= 3").is_err());
+    }
+
+    #[test]
+    fn test_requirement_with_extras() {
+        let with_extras = |req: &str, extras: &[&str]| {
+            let req: PackageRequirement = req.try_into().unwrap();
+            let extras: Vec<Extra> =
+                extras.iter().map(|e| (*e).try_into().unwrap()).collect();
+            req.with_extras(&extras).unwrap().map(|r| r.to_string())
+        };
+        assert_eq!(with_extras("foo", &[]), Some("foo".into()));
+        assert_eq!(with_extras("foo; extra == 'test'", &[]), None);
+        assert_eq!(
+            with_extras("foo; extra == 'test'", &["Test"]),
+            Some("foo".into())
+        );
+        assert_eq!(
+            with_extras("foo; extra == 'test'", &["docs", "test"]),
+            Some("foo".into())
+        );
+        assert_eq!(with_extras("foo; extra == 'test'", &["docs"]), None);
+        assert_eq!(
+            with_extras("foo; os_name == 'nt' and extra == 'test'", &["test"]),
+            Some(r#"foo; os_name == "nt""#.into())
+        );
+        assert_eq!(
+            with_extras("foo; os_name == 'nt' or extra == 'test'", &[]),
+            Some(r#"foo; os_name == "nt""#.into())
+        );
+    }
+
+    #[test]
+    fn test_requirement_normalized_eq() {
+        let r = |s: &str| Requirement::parse(s, ParseExtra::Allowed).unwrap();
+        assert_eq!(r("foo[a,b]"), r("foo[b,a]"));
+        assert_eq!(r("foo[a,b]"), r("foo[B,a,b]"));
+        assert_eq!(r("Foo.Bar >=1, <2"), r("foo-bar <2,>=1"));
+        assert_eq!(
+            r("foo[x]>=1; python_version >= '3'"),
+            r(r#"foo[x] (>=1) ; python_version>="3""#)
+        );
+        assert_ne!(r("foo[a]"), r("foo[a,b]"));
+        assert_ne!(r("foo >=1"), r("foo >=1.0"));
+        assert_ne!(r("foo; os_name == 'nt'"), r("foo"));
+        assert_ne!(r("foo @ https://a"), r("foo @ https://b"));
+
+        let deduped: HashSet<Requirement> =
+            ["foo[a,b] >=1,<2", "foo[b,a] <2,>=1", "bar"]
+                .into_iter()
+                .map(r)
+                .collect();
+        assert_eq!(deduped.len(), 2);
+    }
+
+    #[test]
+    fn test_requirement_display() {
+        let display = |s: &str| {
+            Requirement::parse(s, ParseExtra::Allowed)
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(display("foo"), "foo");
+        assert_eq!(
+            display("Foo.Bar [ baz,quux ] (>=2,<3)"),
+            "Foo.Bar[baz,quux] >=2,<3"
+        );
+        assert_eq!(
+            display("foo[bar]>=1; extra=='test'"),
+            r#"foo[bar] >=1; extra == "test""#
+        );
+        assert_eq!(
+            display("foo@https://x/foo.whl ; os_name=='nt'"),
+            r#"foo @ https://x/foo.whl ; os_name == "nt""#
+        );
+    }
+
+    #[test]
+    fn test_requirement_accessors() {
+        let r = Requirement::parse(
+            r#"foo[a,b]>=1 ; python_version<"3""#,
+            ParseExtra::Allowed,
+        )
+        .unwrap();
+        assert_eq!(r.name().as_given(), "foo");
+        let extras: Vec<&str> = r.extras().iter().map(|e| e.normalized()).collect();
+        assert_eq!(extras, vec!["a", "b"]);
+        assert_eq!(r.specifiers().to_string(), ">=1");
+        assert_eq!(r.marker().unwrap().to_string(), r#"python_version < "3""#);
+        assert_eq!(r.url(), None);
+
+        let r = Requirement::parse("foo", ParseExtra::Allowed).unwrap();
+        assert!(r.extras().is_empty());
+        assert!(r.specifiers().is_empty());
+        assert!(r.marker().is_none());
+    }
+
+    #[test]
+    fn test_decoded_url() {
+        let decoded = |s: &str| {
+            Requirement::parse(s, ParseExtra::NotAllowed)
+                .unwrap()
+                .decoded_url()
+        };
+        let r = Requirement::parse(
+            "foo @ file:///tmp/path%20with%20space/foo-1.0.tar.gz",
+            ParseExtra::NotAllowed,
+        )
+        .unwrap();
+        assert_eq!(
+            r.decoded_url().unwrap().as_deref(),
+            Some("file:///tmp/path with space/foo-1.0.tar.gz")
+        );
+        // the original spelling is kept for url() and Display
+        assert_eq!(
+            r.url(),
+            Some("file:///tmp/path%20with%20space/foo-1.0.tar.gz")
+        );
+        assert_eq!(
+            r.to_string(),
+            "foo @ file:///tmp/path%20with%20space/foo-1.0.tar.gz"
+        );
+
+        // only one layer of decoding
+        assert_eq!(
+            decoded("foo @ https://example.com/a%2520b.whl")
+                .unwrap()
+                .as_deref(),
+            Some("https://example.com/a%20b.whl")
+        );
+        assert_eq!(
+            decoded("foo @ https://example.com/%C3%A9.whl")
+                .unwrap()
+                .as_deref(),
+            Some("https://example.com/é.whl")
+        );
+        assert_eq!(decoded("foo >= 1.0").unwrap(), None);
+        assert!(decoded("foo @ file:///tmp/%FF.whl").is_err());
+    }
+
+    #[test]
+    fn test_url_requirements() {
+        let r = Requirement::parse(
+            "package @ https://example.com/package-1.0-py3-none-any.whl",
+            ParseExtra::NotAllowed,
+        )
+        .unwrap();
+        assert_eq!(r.name().as_given(), "package");
+        assert_eq!(
+            r.url(),
+            Some("https://example.com/package-1.0-py3-none-any.whl")
+        );
+        assert!(r.specifiers().is_empty());
+        assert!(r.marker().is_none());
+
+        let r = Requirement::parse(
+            r#"foo[bar] @ file:///tmp/foo.tar.gz ; python_version < "3.8""#,
+            ParseExtra::NotAllowed,
+        )
+        .unwrap();
+        assert_eq!(r.url(), Some("file:///tmp/foo.tar.gz"));
+        assert_eq!(r.extras().len(), 1);
+        assert_eq!(r.marker().unwrap().to_string(), r#"python_version < "3.8""#);
+        assert_eq!(
+            r.to_string(),
+            r#"foo[bar] @ file:///tmp/foo.tar.gz ; python_version < "3.8""#
+        );
+
+        let err = Requirement::parse("foo>=1 @ https://x", ParseExtra::NotAllowed)
+            .unwrap_err();
+        assert!(format!("{:#}", err)
+            .contains("a direct URL reference can't also have version specifiers"));
+        assert!(Requirement::parse("foo @", ParseExtra::NotAllowed).is_err());
+    }
+
+    #[test]
+    fn test_requirement_builder() {
+        let name = |s: &str| -> PackageName { s.try_into().unwrap() };
+        let marker: StandaloneMarkerExpr = "python_version < '3.8'".try_into().unwrap();
+        let built = Requirement::builder(name("Foo"))
+            .extra("bar".try_into().unwrap())
+            .specifier(Specifier::new(CompareOp::GreaterThanEqual, "1.0").unwrap())
+            .specifiers(&"<2".try_into().unwrap())
+            .marker(marker.0.clone())
+            .build()
+            .unwrap();
+        assert_eq!(
+            built.to_string(),
+            r#"Foo[bar] >=1.0,<2; python_version < "3.8""#
+        );
+        assert_eq!(
+            built,
+            Requirement::parse(&built.to_string(), ParseExtra::NotAllowed).unwrap()
+        );
+
+        let built = Requirement::builder(name("foo"))
+            .url("https://example.com/foo.whl")
+            .marker(marker.0)
+            .build()
+            .unwrap();
+        assert_eq!(
+            built.to_string(),
+            r#"foo @ https://example.com/foo.whl ; python_version < "3.8""#
+        );
+
+        let bare = Requirement::builder(name("foo")).build().unwrap();
+        assert_eq!(bare.to_string(), "foo");
+
+        let err = Requirement::builder(name("foo"))
+            .url("https://example.com/foo.whl")
+            .specifier(Specifier::new(CompareOp::Equal, "1.0").unwrap())
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "a direct URL reference can't also have version specifiers"
+        );
+        for bad_url in ["", "https://example.com/a b.whl"] {
+            let built = Requirement::builder(name("foo")).url(bad_url).build();
+            assert!(built.is_err());
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_requirement_serde() {
+        let r = Requirement::parse(
+            "Foo.Bar[baz] >= 1.0, < 2; extra == 'test'",
+            ParseExtra::Allowed,
+        )
+        .unwrap();
+        let json = serde_json::to_string(&r).unwrap();
+        assert_eq!(json, r#""Foo.Bar[baz] >=1.0,<2; extra == \"test\"""#);
+        let roundtrip: Requirement = serde_json::from_str(&json).unwrap();
+        assert_eq!(roundtrip, r);
+
+        let bad: serde_json::Result<Requirement> = serde_json::from_str(r#""foo >""#);
+        assert!(bad.is_err());
+    }
+
+    fn linux_cpython_311() -> marker::MarkerEnvironment {
+        marker::MarkerEnvironment {
+            implementation_name: "cpython".into(),
+            implementation_version: "3.11.4".into(),
+            os_name: "posix".into(),
+            platform_machine: "x86_64".into(),
+            platform_python_implementation: "CPython".into(),
+            platform_release: "6.1.0-10-amd64".into(),
+            platform_system: "Linux".into(),
+            platform_version: "#1 SMP PREEMPT_DYNAMIC Debian 6.1.38-1".into(),
+            python_full_version: "3.11.4".into(),
+            python_version: "3.11".into(),
+            sys_platform: "linux".into(),
+        }
+    }
+
+    #[test]
+    fn test_marker_evaluate() {
+        let env = linux_cpython_311();
+        let extras: Vec<Extra> = vec!["Security".try_into().unwrap()];
+        let cases = [
+            (r#"python_version >= "3.8""#, true),
+            // version comparison, not string comparison ("3.11" < "3.9" as strings)
+            (r#"python_version < "3.9""#, false),
+            (r#"python_full_version >= "3.11.4""#, true),
+            (r#"sys_platform == "win32""#, false),
+            (
+                r#"os_name == "posix" and platform_machine == "x86_64""#,
+                true,
+            ),
+            (r#"platform_python_implementation != "PyPy""#, true),
+            (
+                r#"implementation_name == "cpython" and python_version ~= "3.10""#,
+                true,
+            ),
+            // not a valid version on the lhs, so it's a lexical comparison
+            (r#"platform_release >= "6""#, true),
+            (r#"platform_system < "Darwin""#, false),
+            (r#"extra == "security""#, true),
+            (r#"extra == "socks""#, false),
+        ];
+        for (marker, expected) in cases {
+            let r = Requirement::parse(&format!("foo; {marker}"), ParseExtra::Allowed)
+                .unwrap();
+            let expr = r.env_marker_expr.unwrap();
+            println!("{marker}");
+            assert_eq!(expr.evaluate(&env, &extras).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_marker_environment_for_python() {
+        let env = marker::MarkerEnvironment::for_python("3.11.4").unwrap();
+        assert_eq!(env.python_full_version, "3.11.4");
+        assert_eq!(env.python_version, "3.11");
+
+        let env = marker::MarkerEnvironment {
+            sys_platform: "win32".into(),
+            ..marker::MarkerEnvironment::for_python("3.12.0rc1").unwrap()
+        };
+        assert_eq!(env.python_version, "3.12");
+        assert_eq!(env.python_full_version, "3.12.0rc1");
+        assert_eq!(env.sys_platform, "win32");
+
+        assert_eq!(
+            marker::MarkerEnvironment::for_python("3")
+                .unwrap()
+                .python_version,
+            "3.0"
+        );
+        assert!(marker::MarkerEnvironment::for_python("three point eleven").is_err());
+    }
+
+    #[test]
+    fn test_every_standard_marker_variable() {
+        let env = marker::MarkerEnvironment {
+            implementation_name: "cpython".into(),
+            implementation_version: "3.11.4".into(),
+            os_name: "posix".into(),
+            platform_machine: "x86_64".into(),
+            platform_python_implementation: "CPython".into(),
+            platform_release: "6.1.0-9-amd64".into(),
+            platform_system: "Linux".into(),
+            platform_version: "#1 SMP PREEMPT_DYNAMIC Debian 6.1.27-1".into(),
+            sys_platform: "linux".into(),
+            ..marker::MarkerEnvironment::for_python("3.11.4").unwrap()
+        };
+        for (var, matching, other) in [
+            ("implementation_name", "cpython", "pypy"),
+            ("implementation_version", "3.11.4", "3.10"),
+            ("os_name", "posix", "nt"),
+            ("platform_machine", "x86_64", "arm64"),
+            ("platform_python_implementation", "CPython", "PyPy"),
+            ("platform_release", "6.1.0-9-amd64", "22.1.0"),
+            ("platform_system", "Linux", "Darwin"),
+            (
+                "platform_version",
+                "#1 SMP PREEMPT_DYNAMIC Debian 6.1.27-1",
+                "x",
+            ),
+            ("python_full_version", "3.11.4", "3.11.5"),
+            ("python_version", "3.11", "3.10"),
+            ("sys_platform", "linux", "win32"),
+        ] {
+            assert!(marker::PEP508_VARIABLES.contains(&var));
+            let applies = |value: &str| {
+                let req: UserRequirement =
+                    format!("foo; {var} == {value:?}").try_into().unwrap();
+                assert_eq!(
+                    req.marker()
+                        .unwrap()
+                        .variables()
+                        .into_iter()
+                        .collect::<Vec<_>>(),
+                    [var]
+                );
+                req.applies(&env, &[]).unwrap()
+            };
+            assert!(applies(matching), "{var}");
+            assert!(!applies(other), "{var}");
+        }
+        // PEP 508 also has 'extra', which the environment doesn't provide
+        assert_eq!(marker::PEP508_VARIABLES.len(), 12);
+    }
+
+    #[test]
+    fn test_marker_environment_validate() {
+        let good = linux_cpython_311();
+        good.validate().unwrap();
+        // round trips through serde, which validates
+        #[cfg(feature = "serde")]
+        {
+            let json = serde_json::to_string(&good).unwrap();
+            let parsed: marker::MarkerEnvironment =
+                serde_json::from_str(&json).unwrap();
+            assert_eq!(parsed, good);
+        }
+        assert_eq!(marker::MarkerEnvironment::from_vars(&good).unwrap(), good);
+
+        let bad = marker::MarkerEnvironment {
+            python_full_version: "3.x".into(),
+            ..linux_cpython_311()
+        };
+        let err = bad.validate().unwrap_err().to_string();
+        assert!(err.contains("python_full_version \"3.x\""), "{}", err);
+        #[cfg(feature = "serde")]
+        {
+            let json = serde_json::to_string(&bad).unwrap();
+            let err =
+                serde_json::from_str::<marker::MarkerEnvironment>(&json).unwrap_err();
+            assert!(err.to_string().contains("python_full_version"), "{}", err);
+        }
+
+        // every problem gets reported
+        let bad = marker::MarkerEnvironment {
+            python_version: "3.10".into(),
+            implementation_version: "latest".into(),
+            ..linux_cpython_311()
+        };
+        let err = bad.validate().unwrap_err().to_string();
+        assert!(err.contains("python_version \"3.10\""), "{}", err);
+        assert!(err.contains("implementation_version \"latest\""), "{}", err);
+
+        // implementation_version can be left unknown
+        marker::MarkerEnvironment::for_python("3.11.4")
+            .unwrap()
+            .validate()
+            .unwrap();
+
+        #[cfg(feature = "serde")]
+        {
+            let mut vars: HashMap<String, String> =
+                serde_json::from_str(&serde_json::to_string(&good).unwrap()).unwrap();
+            vars.remove("sys_platform");
+            let err = marker::MarkerEnvironment::from_vars(&vars).unwrap_err();
+            assert_eq!(err.to_string(), "missing marker variables: sys_platform");
+        }
+    }
+
+    #[test]
+    fn test_marker_version_vs_string_comparison() {
+        let env = linux_cpython_311();
+        let eval = |m: &str| {
+            let expr: StandaloneMarkerExpr = m.try_into().unwrap();
+            expr.0.eval(&env)
+        };
+        // version-valued variables use PEP 440 ordering, from either side
+        assert!(!eval(r#"python_version == "3.1""#).unwrap());
+        assert!(eval(r#"python_version > "3.9""#).unwrap());
+        assert!(eval(r#""3.9" < python_version"#).unwrap());
+        assert!(eval(r#"python_version == "3.11.0""#).unwrap());
+        assert!(eval(r#"python_version == "3.*""#).unwrap());
+        assert!(eval(r#"python_full_version >= "3.11.4.0""#).unwrap());
+        assert!(eval(r#"implementation_version < "3.12a1""#).unwrap());
+        let env_310 = marker::MarkerEnvironment {
+            python_version: "3.10".into(),
+            python_full_version: "3.10.2".into(),
+            ..linux_cpython_311()
+        };
+        let expr: StandaloneMarkerExpr =
+            r#"python_version < "3.9""#.try_into().unwrap();
+        assert!(!expr.0.eval(&env_310).unwrap());
+        // ...and don't quietly fall back to string comparison
+        assert!(eval(r#"python_version >= "three""#).is_err());
+        // except for ===, which is a string comparison by definition
+        assert!(eval(r#"python_version === "3.11""#).unwrap());
+        assert!(!eval(r#"python_version === "3.11.0""#).unwrap());
+
+        // Other variables compare as strings, unless both sides happen to be valid
+        // versions.
+        assert!(!eval(r#"platform_machine >= "x86_64_v2""#).unwrap());
+        assert!(eval(r#"sys_platform > "darwin""#).unwrap());
+        assert!(eval(r#"platform_release >= "6.1.0-1""#).unwrap());
+        let env_510 = marker::MarkerEnvironment {
+            platform_release: "5.10".into(),
+            ..linux_cpython_311()
+        };
+        let expr: StandaloneMarkerExpr =
+            r#"platform_release >= "5.9""#.try_into().unwrap();
+        assert!(expr.0.eval(&env_510).unwrap());
+    }
+
+    #[test]
+    fn test_marker_in_not_in() {
+        let marker = |s: &str| {
+            Requirement::parse(&format!("foo; {s}"), ParseExtra::NotAllowed)
+                .unwrap()
+                .env_marker_expr
+                .unwrap()
+        };
+        let mut env = linux_cpython_311();
+        for (machine, is_arm) in [("arm64", true), ("armv7l", true), ("x86_64", false)]
+        {
+            env.platform_machine = machine.into();
+            assert_eq!(
+                marker(r#""arm" in platform_machine"#)
+                    .evaluate(&env, &[])
+                    .unwrap(),
+                is_arm
+            );
+            assert_eq!(
+                marker(r#""arm" not in platform_machine"#)
+                    .evaluate(&env, &[])
+                    .unwrap(),
+                !is_arm
+            );
+        }
+
+        // variable on the left, literal on the right
+        assert!(marker("sys_platform in 'linux darwin'")
+            .evaluate(&env, &[])
+            .unwrap());
+        assert!(!marker("sys_platform not in 'linux darwin'")
+            .evaluate(&env, &[])
+            .unwrap());
+        // both literals, or both variables
+        assert!(marker("'a' in 'abc'").evaluate(&env, &[]).unwrap());
+        assert!(marker("python_version in python_full_version")
+            .evaluate(&env, &[])
+            .unwrap());
+    }
+
+    #[test]
+    fn test_marker_simplify_partial_env() {
+        use marker::{PartialMarkerEnvironment, Simplified};
+
+        let env = PartialMarkerEnvironment {
+            vars: [("python_version", "3.11"), ("os_name", "posix")]
+                .into_iter()
+                .map(|(k, v)| (k.to_owned(), v.to_owned()))
+                .collect(),
+        };
+        let simplify = |m: &str| {
+            let expr: StandaloneMarkerExpr = m.try_into().unwrap();
+            expr.0.simplify(&env).to_string()
+        };
+        assert_eq!(simplify("python_version >= '3'"), "true");
+        assert_eq!(simplify("python_version < '3'"), "false");
+        assert_eq!(
+            simplify("python_version >= '3' and sys_platform == 'linux'"),
+            r#"sys_platform == "linux""#
+        );
+        assert_eq!(
+            simplify("python_version < '3' and sys_platform == 'linux'"),
+            "false"
+        );
+        assert_eq!(
+            simplify("sys_platform == 'linux' or os_name == 'posix'"),
+            "true"
+        );
+        assert_eq!(
+            simplify(
+                "(sys_platform == 'win32' or os_name == 'nt') and \
+                 (platform_machine == 'x86_64' or python_version >= '3.8')"
+            ),
+            r#"sys_platform == "win32""#
+        );
+        // nothing known: unchanged
+        let expr: StandaloneMarkerExpr =
+            "sys_platform == 'linux' and platform_machine == 'arm64'"
+                .try_into()
+                .unwrap();
+        assert_eq!(
+            expr.0.simplify(&PartialMarkerEnvironment::default()),
+            Simplified::Expr(expr.0.clone())
+        );
+        // a full environment works too
+        assert_eq!(expr.0.simplify(&linux_cpython_311()).to_string(), "false");
+    }
+
+    #[test]
+    fn test_marker_variables() {
+        let vars = |m: &str| {
+            let expr: marker::EnvMarkerExpr =
+                super::super::reqparse::marker(m, ParseExtra::Allowed).unwrap();
+            expr.variables().into_iter().collect::<Vec<_>>()
+        };
+        assert_eq!(
+            vars(
+                "(python_version < '3.8' or os.name == 'nt') and \
+                 'linux' in sys_platform and python_version != '3.5' and extra == 'x'"
+            ),
+            // legacy spellings come out normalized
+            ["extra", "os_name", "python_version", "sys_platform"]
+        );
+        assert_eq!(vars("'a' == 'b'"), Vec::<String>::new());
+        assert_eq!(vars("os_name == os_name"), ["os_name"]);
+    }
+
+    #[test]
+    fn test_unknown_marker_variables() {
+        use marker::VariableStrictness::*;
+
+        for bad in [
+            "foo; python_ver >= '3.8'",
+            "foo; python_versionx >= '3.8'",
+            "foo; os_name == 'nt' and 'x' in python_ver",
+        ] {
+            let err = Requirement::parse(bad, ParseExtra::Allowed).unwrap_err();
+            let msg = format!("{:#}", err);
+            println!("{msg}");
+            assert!(msg.contains("unknown environment marker variable"));
+            assert!(msg.contains(r#""python_ver"#));
+        }
+        let err =
+            StandaloneMarkerExpr::parse("sys_plaform == 'linux'", Strict).unwrap_err();
+        assert!(err.downcast_ref::<VocabError>().is_some());
+        assert!(format!("{:#}", err).contains(r#""sys_plaform""#));
+        // 'extra' is still only allowed where it makes sense
+        assert!(StandaloneMarkerExpr::parse("extra == 'test'", AllowUnknown).is_err());
+
+        // Requirement::parse is strict, but can be told otherwise
+        let vendor = "foo; python_version >= '3.8' and my_tool_flavor == 'fast'";
+        assert!(Requirement::parse(vendor, ParseExtra::Allowed).is_err());
+        let r =
+            Requirement::parse_with(vendor, ParseExtra::Allowed, AllowUnknown).unwrap();
+        assert!(r.marker().unwrap().variables().contains("my_tool_flavor"));
+        // package metadata is lenient, user input is strict
+        assert!(PackageRequirement::try_from(vendor).is_ok());
+        assert!(UserRequirement::try_from(vendor).is_err());
+        let metadata = WheelCoreMetadata::parse(
+            format!(
+                "Metadata-Version: 2.1\nName: bar\nVersion: 1.0\n\
+                 Requires-Dist: {vendor}\n"
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(metadata.requires_dist.len(), 1);
+
+        // and so is the builder
+        let build = |variables| {
+            Requirement::builder("foo".try_into().unwrap())
+                .marker(r.marker().unwrap().clone())
+                .variables(variables)
+                .build()
+        };
+        assert!(build(Strict).is_err());
+        assert_eq!(build(AllowUnknown).unwrap(), r);
+        assert!(Requirement::builder("foo".try_into().unwrap())
+            .marker(r.marker().unwrap().clone())
+            .build()
+            .is_err());
+
+        let expr = StandaloneMarkerExpr::parse(
+            "python_version >= '3.8' and my_tool_flavor == 'fast'",
+            AllowUnknown,
+        )
+        .unwrap();
+        assert_eq!(
+            expr.0.variables().into_iter().collect::<Vec<_>>(),
+            ["my_tool_flavor", "python_version"]
+        );
+        let env: HashMap<&str, &str> =
+            [("python_version", "3.11"), ("my_tool_flavor", "fast")].into();
+        assert!(expr.0.eval(&env).unwrap());
+        // and round-trips
+        assert_eq!(
+            expr.to_string(),
+            r#"python_version >= "3.8" and my_tool_flavor == "fast""#
+        );
+    }
+
+    #[test]
+    fn test_marker_precedence() {
+        let marker = |s: &str| {
+            Requirement::parse(&format!("foo; {s}"), ParseExtra::Allowed)
+                .unwrap()
+                .env_marker_expr
+                .unwrap()
+        };
+        let and_or = marker(
+            r#"python_version < "3.8" and (sys_platform == "win32" or extra == "fast")"#,
+        );
+        assert!(matches!(and_or, marker::EnvMarkerExpr::And(..)));
+        assert_eq!(
+            and_or.to_string(),
+            r#"python_version < "3.8" and (sys_platform == "win32" or extra == "fast")"#
+        );
+        // without the parens, 'and' binds tighter
+        let or = marker(
+            r#"python_version < "3.8" and sys_platform == "win32" or extra == "fast""#,
+        );
+        assert!(matches!(or, marker::EnvMarkerExpr::Or(..)));
+        // redundant parens get dropped, needed ones are kept
+        assert_eq!(
+            marker(r#"((os_name == "a") or (os_name == "b" and os_name == "c"))"#)
+                .to_string(),
+            r#"os_name == "a" or os_name == "b" and os_name == "c""#
+        );
+        assert_eq!(
+            marker(r#"(os_name == "a" or os_name == "b") and os_name == "c""#)
+                .to_string(),
+            r#"(os_name == "a" or os_name == "b") and os_name == "c""#
+        );
+
+        let mut env = linux_cpython_311();
+        env.python_version = "3.7".into();
+        let fast: Vec<Extra> = vec!["fast".parse().unwrap()];
+        assert!(!and_or.evaluate(&env, &[]).unwrap());
+        assert!(and_or.evaluate(&env, &fast).unwrap());
+        assert!(or.evaluate(&linux_cpython_311(), &fast).unwrap());
+        assert!(!or.evaluate(&linux_cpython_311(), &[]).unwrap());
+
+        // printing and re-parsing gives something that evaluates the same
+        for expr in [&and_or, &or] {
+            let reparsed = marker(&expr.to_string());
+            for extras in [&[][..], &fast[..]] {
+                assert_eq!(
+                    reparsed.evaluate(&env, extras).unwrap(),
+                    expr.evaluate(&env, extras).unwrap()
+                );
+            }
+        }
+
+        // evaluation short-circuits, so the undefined variable on the right is never
+        // looked up
+        let partial_env = HashMap::from([("os_name", "posix")]);
+        assert!(!marker(r#"os_name == "nt" and sys_platform == "win32""#)
+            .eval(&partial_env)
+            .unwrap());
+        assert!(marker(r#"os_name == "posix" or sys_platform == "win32""#)
+            .eval(&partial_env)
+            .unwrap());
+        assert!(marker(r#"os_name == "posix" and sys_platform == "win32""#)
+            .eval(&partial_env)
+            .is_err());
+    }
+
+    #[test]
+    fn test_requirement_applies() {
+        let env = linux_cpython_311();
+        let docs: Vec<Extra> = vec!["docs".parse().unwrap()];
+        let req = |s: &str| Requirement::parse(s, ParseExtra::Allowed).unwrap();
+
+        assert!(req("sphinx").applies(&env, &[]).unwrap());
+        assert!(req("sphinx").applies(&env, &docs).unwrap());
+
+        let sphinx = req(r#"sphinx >= 5; extra == "docs""#);
+        assert!(!sphinx.applies(&env, &[]).unwrap());
+        assert!(sphinx.applies(&env, &docs).unwrap());
+
+        let windows_docs =
+            req(r#"pywin32; extra == "docs" and sys_platform == "win32""#);
+        assert!(!windows_docs.applies(&env, &docs).unwrap());
+
+        let old_python = req(r#"typing-extensions; python_version < "3.8""#);
+        assert!(!old_python.applies(&env, &docs).unwrap());
+    }
+
+    #[test]
+    fn test_marker_evaluate_extras() {
+        let env = linux_cpython_311();
+        let r =
+            Requirement::parse("foo; extra == 'docs'", ParseExtra::Allowed).unwrap();
+        let expr = r.env_marker_expr.unwrap();
+        let extras = |names: &[&str]| -> Vec<Extra> {
+            names.iter().map(|n| n.parse().unwrap()).collect()
+        };
+        assert!(!expr.evaluate(&env, &[]).unwrap());
+        assert!(expr.evaluate(&env, &extras(&["docs"])).unwrap());
+        assert!(expr.evaluate(&env, &extras(&["test", "Docs"])).unwrap());
+        assert!(!expr.evaluate(&env, &extras(&["test"])).unwrap());
     }
 
     #[test]
@@ -0,0 +1,240 @@
+use crate::prelude::*;
+
+// Parser for pip's requirements.txt format. There's no real spec for this; it's
+// whatever pip does:
+//
+//   https://pip.pypa.io/en/stable/reference/requirements-file-format/
+//
+// Like pip, we first join lines ending in '\', then strip comments (a '#' at the
+// start of a line or after whitespace -- URLs can contain '#' fragments), then
+// classify what's left. Unlike pip, we don't try to understand every option; we
+// just split them into name and value and let the caller decide what to do with
+// them.
+
+static COMMENT: Lazy<Regex> = Lazy::new(|| Regex::new(r"(^|\s+)#.*$").unwrap());
+// Where the per-requirement options start, e.g. 'foo==1.0 --hash=sha256:...'
+static REQUIREMENT_OPTIONS: Lazy<Regex> = Lazy::new(|| Regex::new(r"\s--").unwrap());
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RequirementLine {
+    /// A requirement, plus any `--hash=algorithm:hexdigest` options attached to it,
+    /// as (algorithm, hexdigest) pairs.
+    Requirement {
+        requirement: UserRequirement,
+        hashes: Vec<(String, String)>,
+    },
+    /// A whole-line comment, without the leading '#'
+    Comment(String),
+    Blank,
+    /// `-r other.txt` / `--requirement other.txt`. The path is relative to the file
+    /// that contains it.
+    Include(String),
+    /// Any other option, like `--index-url https://...` or `--pre`. The name includes
+    /// the leading dashes, exactly as written.
+    Option {
+        name: String,
+        value: Option<String>,
+    },
+}
+
+pub fn parse_requirements_txt(contents: &str) -> Result<Vec<RequirementLine>> {
+    let mut result = Vec::new();
+    for (lineno, line) in logical_lines(contents) {
+        result.push(
+            parse_line(&line).wrap_err_with(|| {
+                format!("parsing requirements.txt line {}", lineno)
+            })?,
+        );
+    }
+    Ok(result)
+}
+
+// Joins continuation lines, returning each logical line along with the (1-based)
+// number of the physical line it started on. Comment lines are never continued,
+// same as pip.
+fn logical_lines(contents: &str) -> Vec<(usize, String)> {
+    let mut result = Vec::new();
+    let mut current: Option<(usize, String)> = None;
+    for (i, line) in contents.lines().enumerate() {
+        let lineno = i + 1;
+        if current.is_none() && line.trim_start().starts_with('#') {
+            result.push((lineno, line.to_owned()));
+            continue;
+        }
+        let (start, mut joined) = current.take().unwrap_or((lineno, String::new()));
+        if let Some(partial) = line.strip_suffix('\\') {
+            joined.push_str(partial);
+            current = Some((start, joined));
+        } else {
+            joined.push_str(line);
+            result.push((start, joined));
+        }
+    }
+    // pip tolerates a dangling '\' on the last line, so we do too
+    if let Some(last) = current {
+        result.push(last);
+    }
+    result
+}
+
+fn parse_line(line: &str) -> Result<RequirementLine> {
+    let trimmed = line.trim();
+    if let Some(comment) = trimmed.strip_prefix('#') {
+        return Ok(RequirementLine::Comment(comment.trim().to_owned()));
+    }
+    let line = COMMENT.replace(trimmed, "");
+    let line = line.trim();
+    if line.is_empty() {
+        Ok(RequirementLine::Blank)
+    } else if line.starts_with('-') {
+        parse_option(line)
+    } else {
+        parse_requirement(line)
+    }
+}
+
+fn parse_requirement(line: &str) -> Result<RequirementLine> {
+    let (requirement, options) = match REQUIREMENT_OPTIONS.find(line) {
+        Some(m) => line.split_at(m.start()),
+        None => (line, ""),
+    };
+    let mut hashes = Vec::new();
+    let mut options = options.split_whitespace();
+    while let Some(option) = options.next() {
+        let value = match option.split_once('=') {
+            Some(("--hash", value)) => value,
+            None if option == "--hash" => options
+                .next()
+                .ok_or_else(|| eyre!("--hash needs a value"))?,
+            _ => bail!("unsupported requirement option {:?}", option),
+        };
+        let (algorithm, digest) = value.split_once(':').ok_or_else(|| {
+            eyre!("invalid hash {:?}: expected 'algorithm:hexdigest'", value)
+        })?;
+        hashes.push((algorithm.to_owned(), digest.to_owned()));
+    }
+    Ok(RequirementLine::Requirement {
+        requirement: requirement.trim().try_into()?,
+        hashes,
+    })
+}
+
+fn parse_option(line: &str) -> Result<RequirementLine> {
+    let (name, value) = if line.starts_with("--") {
+        match line.find(|c: char| c == '=' || c.is_whitespace()) {
+            Some(i) => (&line[..i], &line[i + 1..]),
+            None => (line, ""),
+        }
+    } else {
+        // short options: -rfoo.txt and -r foo.txt are both fine
+        let split = line.char_indices().nth(2).map_or(line.len(), |(i, _)| i);
+        line.split_at(split)
+    };
+    let value = Some(value.trim()).filter(|v| !v.is_empty());
+    match name {
+        "-r" | "--requirement" => Ok(RequirementLine::Include(
+            value
+                .ok_or_else(|| eyre!("{} needs a filename", name))?
+                .into(),
+        )),
+        _ => Ok(RequirementLine::Option {
+            name: name.into(),
+            value: value.map(|v| v.into()),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use indoc::indoc;
+
+    fn req(s: &str) -> RequirementLine {
+        RequirementLine::Requirement {
+            requirement: s.try_into().unwrap(),
+            hashes: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_parse_requirements_txt() {
+        let lines = parse_requirements_txt(indoc! {r#"
+            # top comment
+            trio >= 0.22  # trailing comment
+
+            foo @ https://example.com/foo.whl#sha256=abc
+            numpy \
+              >= 1.20, \
+              < 2; python_version >= "3.8"
+            # comments don't continue \
+            -r other.txt
+            -rshort.txt
+            --requirement=long.txt
+            --index-url https://example.com/simple
+            --pre
+        "#})
+        .unwrap();
+        assert_eq!(
+            lines,
+            vec![
+                RequirementLine::Comment("top comment".into()),
+                req("trio >= 0.22"),
+                RequirementLine::Blank,
+                req("foo @ https://example.com/foo.whl#sha256=abc"),
+                req(r#"numpy >= 1.20, < 2; python_version >= "3.8""#),
+                RequirementLine::Comment("comments don't continue \\".into()),
+                RequirementLine::Include("other.txt".into()),
+                RequirementLine::Include("short.txt".into()),
+                RequirementLine::Include("long.txt".into()),
+                RequirementLine::Option {
+                    name: "--index-url".into(),
+                    value: Some("https://example.com/simple".into()),
+                },
+                RequirementLine::Option {
+                    name: "--pre".into(),
+                    value: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_requirements_txt_hashes() {
+        let lines = parse_requirements_txt(indoc! {"
+            foo==1.0 --hash=sha256:abc123 \\
+                --hash sha256:def456
+            bar==2.0 --hash=sha512:0f0f
+        "})
+        .unwrap();
+        assert_eq!(
+            lines,
+            vec![
+                RequirementLine::Requirement {
+                    requirement: "foo==1.0".try_into().unwrap(),
+                    hashes: vec![
+                        ("sha256".into(), "abc123".into()),
+                        ("sha256".into(), "def456".into()),
+                    ],
+                },
+                RequirementLine::Requirement {
+                    requirement: "bar==2.0".try_into().unwrap(),
+                    hashes: vec![("sha512".into(), "0f0f".into())],
+                },
+            ]
+        );
+
+        assert!(parse_requirements_txt("foo --hash=abc123\n").is_err());
+        assert!(parse_requirements_txt("foo --hash\n").is_err());
+        assert!(parse_requirements_txt("foo --global-option=x\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_requirements_txt_errors() {
+        let err = parse_requirements_txt("trio\n\nfoo [ \\\n  !!!\n").unwrap_err();
+        assert!(format!("{:#}", err).contains("line 3"));
+
+        assert!(parse_requirements_txt("-r\n").is_err());
+        // extra markers are only for package metadata
+        assert!(parse_requirements_txt("foo; extra == 'test'\n").is_err());
+    }
+}
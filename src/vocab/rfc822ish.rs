@@ -1,6 +1,8 @@
 use crate::prelude::*;
 
-pub type Fields = HashMap<String, Vec<String>>;
+// (name, value) pairs in the order they appeared, with names spelled as they
+// were in the source. Field names are case-insensitive, so lookups ignore case.
+pub type Fields = Vec<(String, String)>;
 
 #[cfg_attr(test, derive(Debug, Deserialize, PartialEq))]
 pub struct RFC822ish {
@@ -55,7 +57,7 @@ peg::parser! {
 
         rule field() -> (String, String)
             = n:field_name() field_separator() v:field_value()
-                { (n.to_ascii_lowercase(), v.to_owned()) }
+                { (n.to_owned(), v.to_owned()) }
 
         rule fields() -> Vec<(String, String)>
             = field() ** line_ending()
@@ -73,13 +75,7 @@ peg::parser! {
         // absorb.
         pub rule rfc822ish() -> RFC822ish
             = f:fields() body:(trailing_body()?) line_ending()?
-                 {
-                     let mut fields = Fields::new();
-                     for (name, value) in f {
-                         fields.entry(name).or_insert(Vec::new()).push(value)
-                     };
-                     RFC822ish { fields, body, }
-                 }
+                 { RFC822ish { fields: f, body, } }
     }
 }
 
@@ -88,11 +84,37 @@ impl RFC822ish {
         Ok(rfc822ish_parser::rfc822ish(input)?)
     }
 
+    /// The message body: everything after the blank line that ends the headers. Empty
+    /// if there was no blank line.
+    pub fn body(&self) -> &str {
+        self.body.as_deref().unwrap_or("")
+    }
+
+    /// Iterates over all remaining (name, value) pairs, in source order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.fields
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.fields
+            .iter()
+            .any(|(name, _)| name.eq_ignore_ascii_case(key))
+    }
+
     pub fn take_all(&mut self, key: &str) -> Vec<String> {
-        match self.fields.remove(&key.to_ascii_lowercase()) {
-            Some(vec) => vec,
-            None => Vec::new(),
+        let mut taken = Vec::new();
+        let mut kept = Vec::new();
+        for (name, value) in self.fields.drain(..) {
+            if name.eq_ignore_ascii_case(key) {
+                taken.push(value);
+            } else {
+                kept.push((name, value));
+            }
         }
+        self.fields = kept;
+        taken
     }
 
     pub fn maybe_take_the(&mut self, key: &str) -> Result<Option<String>> {
@@ -138,17 +160,24 @@ mod test {
                 "#},
                 expected: indoc! {r#"
                    {
-                     "fields": { "a": ["b"], "c": ["d\n   continued"]},
+                     "fields": [["A", "b"], ["C", "d\n   continued"]],
                      "body": "this is the\nbody!\n"
                    }
                 "#},
             },
+            T {
+                given: "Description: first\n\tsecond\n    third\nOther: x\n",
+                expected: r#"{"fields": [
+                    ["Description", "first\n\tsecond\n    third"],
+                    ["Other", "x"]
+                ]}"#,
+            },
             T {
                 given: indoc! {r#"
                    no: body
                 "#},
                 expected: indoc! {r#"
-                   {"fields": {"no": ["body"]}}
+                   {"fields": [["no", "body"]]}
                 "#},
             },
             T {
@@ -159,19 +188,24 @@ mod test {
                    duplicate: three
                 "#},
                 expected: indoc! {r#"
-                   {"fields": {"duplicate": ["one", "two", "three"], "another": ["field"]}}
+                   {"fields": [
+                     ["duplicate", "one"],
+                     ["duplicate", "two"],
+                     ["another", "field"],
+                     ["duplicate", "three"]
+                   ]}
                 "#},
             },
             T {
                 given: indoc! {r#"
                 no: trailing newline"#},
                 expected: indoc! {r#"
-                   {"fields": {"no": ["trailing newline"]}}
+                   {"fields": [["no", "trailing newline"]]}
                 "#},
             },
             T {
                 given: "",
-                expected: r#"{"fields": {}}"#,
+                expected: r#"{"fields": []}"#,
             },
         ];
 
@@ -219,5 +253,56 @@ mod test {
             "bar"
         );
         assert_eq!(got.take_the("mixed-CASE").unwrap(), "baz");
+
+        let mut got = RFC822ish::parse("Metadata-Version: 2.1\n").unwrap();
+        // original casing is kept for re-emitting
+        assert_eq!(
+            got.iter().collect::<Vec<_>>(),
+            vec![("Metadata-Version", "2.1")]
+        );
+        assert_eq!(got.take_the("metadata-version").unwrap(), "2.1");
+    }
+
+    #[test]
+    fn test_field_order() {
+        let mut got = RFC822ish::parse(indoc! {r#"
+              Metadata-Version: 2.1
+              Name: foo
+              Requires-Dist: b
+              Version: 1.0
+              Requires-Dist: a
+        "#})
+        .unwrap();
+        assert_eq!(
+            got.iter().collect::<Vec<_>>(),
+            vec![
+                ("Metadata-Version", "2.1"),
+                ("Name", "foo"),
+                ("Requires-Dist", "b"),
+                ("Version", "1.0"),
+                ("Requires-Dist", "a"),
+            ]
+        );
+
+        assert!(got.contains("requires-dist"));
+        assert_eq!(got.take_all("REQUIRES-DIST"), vec!["b", "a"]);
+        assert!(!got.contains("requires-dist"));
+        // the rest keep their relative order
+        assert_eq!(
+            got.iter().map(|(name, _)| name).collect::<Vec<_>>(),
+            vec!["Metadata-Version", "Name", "Version"]
+        );
+    }
+
+    #[test]
+    fn test_body() {
+        let got = RFC822ish::parse("A: b\n\nthe body\n\nhas: no fields\n").unwrap();
+        assert_eq!(got.body(), "the body\n\nhas: no fields\n");
+
+        let got = RFC822ish::parse("A: b\n").unwrap();
+        assert_eq!(got.body(), "");
+
+        let got = RFC822ish::parse("A: b\n\n").unwrap();
+        assert_eq!(got.body(), "");
     }
 }
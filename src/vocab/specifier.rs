@@ -1,35 +1,201 @@
 use crate::prelude::*;
+use once_cell::sync::OnceCell;
 use std::ops::Range;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// A single version comparison, like ">= 1.2" or "== 2.*".
+///
+/// The fields are private, so there's no struct-literal construction: build one with
+/// `Specifier::new` (which validates the value), `Specifier::new_unchecked`, or by
+/// parsing, and read it back with `op()` and `value()`. That's what keeps the cached
+/// data below correct -- it's derived from `op` and `value`, which can't change after
+/// construction, so it can never go stale, and equality ignores it.
+#[derive(Clone, Derivative)]
+#[derivative(Debug, PartialEq, Eq)]
 pub struct Specifier {
-    pub op: CompareOp,
-    pub value: String,
+    op: CompareOp,
+    value: String,
+    // Expanding into ranges means re-parsing the version, so we do it lazily on first
+    // use and then hang onto the result. Errors aren't cached; they're cheap to
+    // regenerate, and this way every caller sees the original error.
+    #[derivative(Debug = "ignore", PartialEq = "ignore")]
+    ranges: OnceCell<Vec<Range<Version>>>,
+    // The value parsed as a version (without any trailing ".*"), or None if it isn't
+    // one. Cached for the same reason: satisfied_by needs it for every candidate.
+    #[derivative(Debug = "ignore", PartialEq = "ignore")]
+    version: OnceCell<Option<Version>>,
 }
 
 impl Specifier {
+    /// Makes a specifier, checking up front that `value` makes sense for `op`, the
+    /// same way strict parsing does: it has to be a valid version, and wildcards and
+    /// local versions are only allowed where PEP 440 allows them. So ">= 1.*" is an
+    /// error. '===' takes any string.
+    pub fn new(op: CompareOp, value: &str) -> Result<Specifier> {
+        let spec = Specifier::new_unchecked(op, value.into());
+        spec.check()?;
+        Ok(spec)
+    }
+
+    /// Makes a specifier without looking at `value`. If it's invalid, that's reported
+    /// when the specifier is used (this is how lenient parsing works).
+    pub fn new_unchecked(op: CompareOp, value: String) -> Specifier {
+        Specifier {
+            op,
+            value,
+            ranges: OnceCell::new(),
+            version: OnceCell::new(),
+        }
+    }
+
+    fn check(&self) -> Result<()> {
+        if self.op != CompareOp::ArbitraryEqual {
+            self.ranges()
+                .wrap_err_with(|| VocabError::InvalidSpecifier {
+                    input: self.to_string(),
+                })?;
+        }
+        Ok(())
+    }
+
+    pub fn op(&self) -> CompareOp {
+        self.op
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
     pub fn satisfied_by(&self, version: &Version) -> Result<bool> {
-        Ok(self.to_ranges()?.into_iter().any(|r| r.contains(version)))
+        if self.op == CompareOp::ArbitraryEqual {
+            return Ok(self.arbitrary_equal(version));
+        }
+        // Fast path for exact pins like "== 1.2.3", which are most of the specifiers
+        // we see in practice: compare against the (cached) pinned version instead of
+        // building the range. Same rules as exact_range: "== V" also matches V's
+        // local versions, "== V+local" only matches itself. Anything unusual goes the
+        // slow way, so it gets the usual errors.
+        if self.op == CompareOp::Equal && !self.value.ends_with(".*") {
+            if let Some(pin) = self.version() {
+                let ordering = if pin.local_segments().is_empty() {
+                    version.cmp_ignore_local(pin)
+                } else {
+                    version.cmp(pin)
+                };
+                return Ok(ordering == std::cmp::Ordering::Equal);
+            }
+        }
+        Ok(self.ranges()?.iter().any(|r| r.contains(version)))
+    }
+
+    // For Specifiers::explain: None if `version` matches, otherwise why not.
+    fn explain(&self, version: &Version) -> Result<Option<String>> {
+        if self.op == CompareOp::ArbitraryEqual {
+            return Ok((!self.arbitrary_equal(version))
+                .then(|| format!("{} isn't literally {:?}", version, self.value)));
+        }
+        let ranges = self.ranges()?;
+        Ok(if ranges.iter().any(|r| r.contains(version)) {
+            None
+        } else if ranges.first().is_some_and(|r| version < &r.start) {
+            Some(format!("{} is too low", version))
+        } else if ranges.last().is_some_and(|r| version >= &r.end) {
+            Some(format!("{} is too high", version))
+        } else {
+            Some(format!("{} is excluded", version))
+        })
+    }
+
+    fn arbitrary_equal(&self, version: &Version) -> bool {
+        // PEP 440: "arbitrary equality comparisons are simple string equality
+        // operations which do not take into account any of the semantic
+        // information". Like 'packaging', we compare case-insensitively.
+        version.to_string().eq_ignore_ascii_case(&self.value)
+    }
+
+    /// PEP 440 says that pre-releases are excluded by default, unless the specifier
+    /// itself mentions one, like ">= 2.0b1". (But not "< 2.0b1" or "!= 2.0b1", which
+    /// exclude things rather than asking for them.)
+    pub fn mentions_prerelease(&self) -> bool {
+        use CompareOp::*;
+        match self.op {
+            Equal | GreaterThanEqual | LessThanEqual | Compatible | ArbitraryEqual => {
+                self.version().is_some_and(|v| v.is_prerelease())
+            }
+            NotEqual | StrictlyLessThan | StrictlyGreaterThan => false,
+        }
+    }
+
+    // The value as a version, parsed on first use.
+    fn version(&self) -> Option<&Version> {
+        self.version
+            .get_or_init(|| {
+                let vstr = self.value.strip_suffix(".*").unwrap_or(&self.value);
+                Version::try_from(vstr).ok()
+            })
+            .as_ref()
+    }
+
+    /// Like `to_ranges`, but borrows from the cache instead of copying.
+    pub fn ranges(&self) -> Result<&[Range<Version>]> {
+        Ok(self
+            .ranges
+            .get_or_try_init(|| self.op.to_ranges(&self.value))?)
     }
 
     pub fn to_ranges(&self) -> Result<Vec<Range<Version>>> {
-        self.op.to_ranges(&self.value)
+        Ok(self.ranges()?.to_vec())
     }
 }
 
 impl Display for Specifier {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{} {}", self.op, self.value)
+        write!(f, "{}{}", self.op, self.value)
     }
 }
 
-#[derive(
-    Debug, Clone, PartialEq, Eq, SerializeDisplay, DeserializeFromStr, Default,
-)]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(SerializeDisplay, DeserializeFromStr))]
 pub struct Specifiers(pub Vec<Specifier>);
 
 impl Specifiers {
+    /// No specifiers at all, which every version satisfies. Same as `default()`.
+    pub fn empty() -> Specifiers {
+        Specifiers(Vec::new())
+    }
+
+    /// Adds another specifier that versions have to satisfy.
+    pub fn push(&mut self, spec: Specifier) {
+        self.0.push(spec);
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Specifier> {
+        self.0.iter()
+    }
+
+    /// Checks whether `version` satisfies all the specifiers. Pre-releases are only
+    /// accepted if at least one of the specifiers mentions a pre-release; see
+    /// `satisfied_by_with_prereleases` to override that.
     pub fn satisfied_by(&self, version: &Version) -> Result<bool> {
+        let allow_prereleases = self.0.iter().any(|spec| spec.mentions_prerelease());
+        self.satisfied_by_with_prereleases(version, allow_prereleases)
+    }
+
+    pub fn satisfied_by_with_prereleases(
+        &self,
+        version: &Version,
+        allow_prereleases: bool,
+    ) -> Result<bool> {
+        if !allow_prereleases && version.is_prerelease() {
+            return Ok(false);
+        }
         for specifier in &self.0 {
             if !specifier.satisfied_by(&version)? {
                 return Ok(false);
@@ -37,14 +203,235 @@ impl Specifiers {
         }
         Ok(true)
     }
+
+    /// Describes how each specifier treats `version`, for debugging why something
+    /// did or didn't match, e.g. ">=1.2 ✓; <2.0 ✗ (2.5 is too high)". Doesn't
+    /// fail: an invalid specifier is reported in the text, like everything else.
+    pub fn explain(&self, version: &Version) -> String {
+        let mut parts: Vec<String> = self
+            .0
+            .iter()
+            .map(|spec| match spec.explain(version) {
+                Ok(None) => format!("{} ✓", spec),
+                Ok(Some(why)) => format!("{} ✗ ({})", spec, why),
+                Err(err) => format!("{} ✗ (invalid: {})", spec, err),
+            })
+            .collect();
+        if version.is_prerelease() && !self.0.iter().any(|s| s.mentions_prerelease()) {
+            parts.push(format!(
+                "{} is a pre-release, and no specifier mentions one",
+                version
+            ));
+        }
+        if parts.is_empty() {
+            "no specifiers; any version matches".into()
+        } else {
+            parts.join("; ")
+        }
+    }
+
+    /// Returns the versions that satisfy all the specifiers, in their original order.
+    pub fn filter<'a, I>(&self, versions: I) -> Result<Vec<&'a Version>>
+    where
+        I: IntoIterator<Item = &'a Version>,
+    {
+        let mut result = Vec::new();
+        for version in versions {
+            if self.satisfied_by(version)? {
+                result.push(version);
+            }
+        }
+        Ok(result)
+    }
+
+    /// Returns the highest version that satisfies all the specifiers, if any.
+    pub fn highest_match<'a>(
+        &self,
+        versions: &'a [Version],
+    ) -> Result<Option<&'a Version>> {
+        let mut best: Option<&'a Version> = None;
+        for version in versions {
+            // skip the comparison if it can't beat what we already have
+            if let Some(b) = best {
+                if version <= b {
+                    continue;
+                }
+            }
+            if self.satisfied_by(version)? {
+                best = Some(version);
+            }
+        }
+        Ok(best)
+    }
+
+    /// Returns a specifier set that's satisfied exactly when both `self` and `other`
+    /// are. Duplicate specifiers are dropped.
+    pub fn intersect(&self, other: &Specifiers) -> Specifiers {
+        let mut merged = self.0.clone();
+        for spec in &other.0 {
+            if !merged.contains(spec) {
+                merged.push(spec.clone());
+            }
+        }
+        Specifiers(merged)
+    }
+
+    /// If this is a single "== V" (no wildcard), returns V. Anything else -- ranges,
+    /// wildcards, "===", or several specifiers -- gives None, even if it happens to
+    /// only allow one version.
+    pub fn is_exact_pin(&self) -> Option<Version> {
+        match &self.0[..] {
+            [spec] if spec.op == CompareOp::Equal && !spec.value.ends_with(".*") => {
+                spec.value.as_str().try_into().ok()
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns true if there's some version that satisfies all of the specifiers.
+    ///
+    /// Fails if any specifier is invalid, or can't be expressed as a range (i.e.,
+    /// uses '===').
+    pub fn is_satisfiable(&self) -> Result<bool> {
+        Ok(!self.to_ranges()?.is_empty())
+    }
+
+    /// Returns the versions allowed by all the specifiers, as sorted, non-overlapping,
+    /// non-adjacent half-open ranges. (Ignoring the pre-release rules, like the
+    /// per-specifier ranges.)
+    ///
+    /// Fails if any specifier is invalid, or uses '==='.
+    pub fn to_ranges(&self) -> Result<Vec<Range<Version>>> {
+        let mut allowed = vec![VERSION_ZERO.clone()..VERSION_INFINITY.clone()];
+        for spec in &self.0 {
+            allowed = intersect_ranges(&allowed, spec.ranges()?);
+            if allowed.is_empty() {
+                break;
+            }
+        }
+        Ok(union_ranges(&allowed, &[]))
+    }
+
+    /// Returns a copy with the specifiers in a canonical order, so that "<2,>=1" and
+    /// ">=1,<2" both display as ">=1,<2". They're sorted by version, then by
+    /// operator, with lower bounds before upper bounds for the same version. Anything
+    /// whose value doesn't parse as a version (like '===' with an arbitrary string)
+    /// goes at the end, in string order. The set of allowed versions doesn't change.
+    pub fn for_display(&self) -> Specifiers {
+        let key = |spec: &Specifier| {
+            let vstr = spec.value.strip_suffix(".*").unwrap_or(&spec.value);
+            let version = Version::try_from(vstr).ok();
+            (
+                version.is_none(),
+                version,
+                spec.op.display_rank(),
+                spec.value.clone(),
+            )
+        };
+        let mut specs = self.0.clone();
+        specs.sort_by_cached_key(key);
+        Specifiers(specs)
+    }
+
+    /// Returns true if `self` and `other` allow exactly the same versions, however
+    /// they're written: ">=1.0,<2.0" and "<2.0,>=1.0" are equal, and so are "~=1.4" and
+    /// ">=1.4,==1.*". (Going by `to_ranges`, so the pre-release rules aren't
+    /// considered.)
+    ///
+    /// Fails if either side has an invalid specifier, or uses '==='.
+    pub fn semantic_eq(&self, other: &Specifiers) -> Result<bool> {
+        Ok(self.to_ranges()? == other.to_ranges()?)
+    }
+
+    /// Returns true if every version allowed by `self` is also allowed by `other`, so
+    /// that `other` is redundant next to `self`. (Going by `to_ranges`, so the
+    /// pre-release rules aren't considered.)
+    ///
+    /// Fails if either side has an invalid specifier, or uses '==='.
+    pub fn is_subset_of(&self, other: &Specifiers) -> Result<bool> {
+        let ours = self.to_ranges()?;
+        let theirs = other.to_ranges()?;
+        // Both sides are normalized, so adjacent ranges have already been merged and
+        // each of ours has to fit inside a single one of theirs.
+        Ok(ours.iter().all(|r| {
+            theirs
+                .iter()
+                .any(|outer| outer.start <= r.start && r.end <= outer.end)
+        }))
+    }
+
+    /// Returns the versions allowed by either `self` or `other`. A comma-separated
+    /// specifier list can only express intersections, so this gives back ranges (in
+    /// the same form as `to_ranges`) rather than another `Specifiers`.
+    pub fn union(&self, other: &Specifiers) -> Result<Vec<Range<Version>>> {
+        Ok(union_ranges(&self.to_ranges()?, &other.to_ranges()?))
+    }
+}
+
+impl IntoIterator for Specifiers {
+    type Item = Specifier;
+    type IntoIter = std::vec::IntoIter<Specifier>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Specifiers {
+    type Item = &'a Specifier;
+    type IntoIter = std::slice::Iter<'a, Specifier>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+/// Given two unions of half-open ranges, returns an equivalent union that's sorted,
+/// with empty ranges dropped and overlapping or touching ranges merged, so e.g. [1, 2)
+/// and [2, 3) become [1, 3).
+fn union_ranges(a: &[Range<Version>], b: &[Range<Version>]) -> Vec<Range<Version>> {
+    let mut all: Vec<&Range<Version>> =
+        a.iter().chain(b).filter(|r| r.start < r.end).collect();
+    all.sort_by(|x, y| x.start.cmp(&y.start));
+    let mut result: Vec<Range<Version>> = Vec::new();
+    for r in all {
+        match result.last_mut() {
+            Some(last) if r.start <= last.end => {
+                if r.end > last.end {
+                    last.end = r.end.clone();
+                }
+            }
+            _ => result.push(r.clone()),
+        }
+    }
+    result
+}
+
+/// Given two unions of half-open ranges, returns the union of half-open ranges
+/// containing the versions that are in both. Empty ranges are dropped, so the
+/// intersection is empty iff the returned vector is.
+fn intersect_ranges(a: &[Range<Version>], b: &[Range<Version>]) -> Vec<Range<Version>> {
+    let mut result = Vec::new();
+    for ra in a {
+        for rb in b {
+            let start = std::cmp::max(&ra.start, &rb.start);
+            let end = std::cmp::min(&ra.end, &rb.end);
+            if start < end {
+                result.push(start.clone()..end.clone());
+            }
+        }
+    }
+    result
 }
 
+/// Emits the canonical form, e.g. ">=1.2,<2.0". Parsing the result gives back an
+/// equal `Specifiers`.
 impl Display for Specifiers {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mut first = true;
         for spec in &self.0 {
             if !first {
-                write!(f, ", ")?
+                write!(f, ",")?
             }
             first = false;
             write!(f, "{}", spec)?
@@ -53,19 +440,105 @@ impl Display for Specifiers {
     }
 }
 
+/// How picky `Specifiers::parse` is.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SpecifierStrictness {
+    /// Everything has to be valid PEP 440, and is checked up front: each version has
+    /// to parse, and be legal for its operator (so no ">= 1.0.*", "~= 1", or "< 1.0+local").
+    /// The only exception is '===', which takes an arbitrary string.
+    Strict,
+    /// Like pip used to be: a trailing comma is ignored, and the versions are only
+    /// checked for basic syntax. Anything that makes no sense for its operator is
+    /// reported later, when the specifier is actually used. Index pages and package
+    /// metadata are full of things like ">=2.7.*", so that's what we use for those.
+    Lenient,
+}
+
+impl Specifiers {
+    pub fn parse(input: &str, strictness: SpecifierStrictness) -> Result<Specifiers> {
+        let to_parse = match strictness {
+            SpecifierStrictness::Strict => input,
+            SpecifierStrictness::Lenient => {
+                let trimmed = input.trim_end();
+                trimmed.strip_suffix(',').unwrap_or(trimmed).trim_end()
+            }
+        };
+        let specifiers =
+            super::reqparse::versionspec(to_parse).wrap_err_with(|| {
+                VocabError::InvalidSpecifier {
+                    input: input.into(),
+                }
+            })?;
+        if strictness == SpecifierStrictness::Strict {
+            for spec in &specifiers.0 {
+                spec.check()?;
+            }
+        }
+        Ok(specifiers)
+    }
+}
+
+/// Parses strictly; see `SpecifierStrictness`.
 impl TryFrom<&str> for Specifiers {
     type Error = eyre::Report;
 
     fn try_from(input: &str) -> Result<Self, Self::Error> {
-        let specifiers_or_err = super::reqparse::versionspec(input);
-        specifiers_or_err.wrap_err_with(|| {
-            format!("failed to parse versions specifiers from {:?}", input)
-        })
+        Specifiers::parse(input, SpecifierStrictness::Strict)
     }
 }
 
 try_from_str_boilerplate!(Specifiers);
 
+/// The specifiers from a Requires-Python field, which say which Python interpreters a
+/// package supports. Parsing is lenient about syntax, like it is for other metadata,
+/// but unlike a plain `Specifiers`, everything is checked up front: every specifier
+/// has to make sense as a range of interpreter versions, so '===' and local versions
+/// aren't allowed.
+#[derive(Shrinkwrap, Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(DeserializeFromStr, SerializeDisplay))]
+pub struct RequiresPython(Specifiers);
+
+impl RequiresPython {
+    /// Whether an interpreter with the given python_full_version is supported.
+    /// Pre-release interpreters count too: Requires-Python: >=3.8 is meant to allow
+    /// 3.12.0b1, even though ">=3.8" as a package specifier wouldn't.
+    pub fn contains(&self, python_full_version: &Version) -> Result<bool> {
+        self.0
+            .satisfied_by_with_prereleases(python_full_version, true)
+    }
+}
+
+impl Display for RequiresPython {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl TryFrom<&str> for RequiresPython {
+    type Error = eyre::Report;
+
+    fn try_from(input: &str) -> Result<Self, Self::Error> {
+        let specifiers = Specifiers::parse(input, SpecifierStrictness::Lenient)?;
+        for spec in &specifiers.0 {
+            if spec.op == CompareOp::ArbitraryEqual {
+                bail!("'===' isn't allowed in Requires-Python: {:?}", input);
+            }
+            if spec.value.contains('+') {
+                bail!(
+                    "local versions aren't allowed in Requires-Python: {:?}",
+                    input
+                );
+            }
+            spec.ranges().wrap_err_with(|| {
+                format!("invalid Requires-Python specifier {:?}", spec.to_string())
+            })?;
+        }
+        Ok(RequiresPython(specifiers))
+    }
+}
+
+try_from_str_boilerplate!(RequiresPython);
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum CompareOp {
     LessThanEqual,
@@ -75,6 +548,25 @@ pub enum CompareOp {
     GreaterThanEqual,
     StrictlyGreaterThan,
     Compatible,
+    ArbitraryEqual,
+}
+
+impl CompareOp {
+    // Tie-breaker for Specifiers::for_display: things that set a lower bound come
+    // before exact matches, which come before upper bounds.
+    fn display_rank(&self) -> u8 {
+        use CompareOp::*;
+        match self {
+            Compatible => 0,
+            GreaterThanEqual => 1,
+            StrictlyGreaterThan => 2,
+            Equal => 3,
+            ArbitraryEqual => 4,
+            NotEqual => 5,
+            LessThanEqual => 6,
+            StrictlyLessThan => 7,
+        }
+    }
 }
 
 impl Display for CompareOp {
@@ -91,6 +583,7 @@ impl Display for CompareOp {
                 GreaterThanEqual => ">=",
                 StrictlyGreaterThan => ">",
                 Compatible => "~=",
+                ArbitraryEqual => "===",
             }
         )
     }
@@ -109,8 +602,8 @@ impl TryFrom<&str> for CompareOp {
             ">=" => GreaterThanEqual,
             ">" => StrictlyGreaterThan,
             "~=" => Compatible,
-            "===" => bail!("'===' is not implemented"),
-            _ => bail!("unrecognized operator: {:?}", value),
+            "===" => ArbitraryEqual,
+            _ => Err(VocabError::UnknownOperator { op: value.into() })?,
         })
     }
 }
@@ -134,6 +627,11 @@ fn parse_version_wildcard(input: &str) -> Result<(Version, bool)> {
 impl CompareOp {
     pub fn to_ranges(&self, rhs: &str) -> Result<Vec<Range<Version>>> {
         use CompareOp::*;
+        if self == &ArbitraryEqual {
+            // === compares raw strings, so there's no way to express it as a set of
+            // version ranges. Specifier::satisfied_by special-cases it instead.
+            bail!("'===' comparisons can't be converted into version ranges");
+        }
         let (version, wildcard) = parse_version_wildcard(rhs)?;
         Ok(if wildcard {
             if version.0.dev.is_some() || !version.0.local.is_empty() {
@@ -142,6 +640,8 @@ impl CompareOp {
             // == X.* corresponds to the half-open range
             //
             // [X.dev0, (X+1).dev0)
+            //
+            // Both ends are cloned from the original version, so they keep its epoch.
             let mut low = version.clone();
             low.0.dev = Some(0);
             let mut high = version.clone();
@@ -168,81 +668,93 @@ impl CompareOp {
                 _ => bail!("Can't use wildcard with {:?}", self),
             }
         } else {
-            // no wildcards here
-            if self != &Equal && self != &NotEqual {
-                if !version.0.local.is_empty() {
-                    bail!("Operator {:?} cannot be used on a version with a +local suffix", self);
-                }
+            let range = self.exact_range(&version)?;
+            if self == &NotEqual {
+                vec![
+                    VERSION_ZERO.clone()..range.start,
+                    range.end..VERSION_INFINITY.clone(),
+                ]
+            } else {
+                vec![range]
             }
-            match self {
-                // These two are simple
-                LessThanEqual => vec![VERSION_ZERO.clone()..version.next()],
-                GreaterThanEqual => vec![version.clone()..VERSION_INFINITY.clone()],
-                // These are also pretty simple, because we took care of the wildcard
-                // cases up above.
-                Equal => vec![version.clone()..version.next()],
-                NotEqual => vec![
-                    VERSION_ZERO.clone()..version.clone(),
-                    version.next()..VERSION_INFINITY.clone(),
-                ],
-                // "The exclusive ordered comparison >V MUST NOT allow a post-release of
-                // the given version unless V itself is a post release."
-                StrictlyGreaterThan => {
-                    let mut low = version.clone();
-                    if let Some(dev) = &version.0.dev {
-                        low.0.dev = Some(dev + 1);
-                    } else if let Some(post) = &version.0.post {
-                        low.0.post = Some(post + 1);
-                    } else {
-                        // Otherwise, want to increment either the pre-release (a0 ->
-                        // a1), or the "last" release segment. But working with
-                        // pre-releases takes a lot of typing, and there is no "last"
-                        // release segment -- X.Y.Z is just shorthand for
-                        // X.Y.Z.0.0.0.0... So instead, we tack on a .post(INFINITY) and
-                        // hope no-one actually makes a version like this in practice.
-                        low.0.post = Some(u32::MAX);
-                    }
-                    vec![low..VERSION_INFINITY.clone()]
-                }
-                // "The exclusive ordered comparison <V MUST NOT allow a pre-release of
-                // the specified version unless the specified version is itself a
-                // pre-release."
-                StrictlyLessThan => {
-                    if (&version.0.pre, &version.0.dev) == (&None, &None) {
-                        let mut new_max = version.clone();
-                        new_max.0.dev = Some(0);
-                        new_max.0.post = None;
-                        new_max.0.local = vec![];
-                        vec![VERSION_ZERO.clone()..new_max]
-                    } else {
-                        // Otherwise, some kind of pre-release
-                        vec![VERSION_ZERO.clone()..version]
-                    }
+        })
+    }
+
+    /// For the non-wildcard, non-'===' cases: the half-open range of versions that
+    /// the comparison accepts -- or for '!=', the range it rejects.
+    fn exact_range(&self, version: &Version) -> Result<Range<Version>> {
+        use CompareOp::*;
+        if self != &Equal && self != &NotEqual && !version.0.local.is_empty() {
+            bail!(
+                "Operator {:?} cannot be used on a version with a +local suffix",
+                self
+            );
+        }
+        Ok(match self {
+            // These two are simple
+            LessThanEqual => VERSION_ZERO.clone()..version.next(),
+            GreaterThanEqual => version.clone()..VERSION_INFINITY.clone(),
+            // These are also pretty simple, because wildcards are handled
+            // separately, in to_ranges. PEP 440: "== V" matches V and any local version of
+            // V, which is what [V, V.next()) gives us. But "== V+local" only
+            // matches exactly that local version.
+            Equal | NotEqual => {
+                let end = if version.0.local.is_empty() {
+                    version.next()
+                } else {
+                    version.local_successor()
+                };
+                version.clone()..end
+            }
+            // "The exclusive ordered comparison >V MUST NOT allow a post-release of
+            // the given version unless V itself is a post release."
+            StrictlyGreaterThan => {
+                let mut low = version.clone();
+                if let Some(dev) = &version.0.dev {
+                    low.0.dev = Some(dev + 1);
+                } else if let Some(post) = &version.0.post {
+                    low.0.post = Some(post + 1);
+                } else {
+                    // Otherwise, we have to skip over V's post-releases. There's no
+                    // smallest version after those (X.Y.Z is just shorthand for
+                    // X.Y.Z.0.0.0.0...), so we use a special bound that sorts
+                    // right after them.
+                    low = version.after_post_releases();
                 }
-                // ~= X.Y.suffixes is the same as >= X.Y.suffixes && == X.*
-                // So it's a half-open range:
-                //   [X.Y.suffixes, (X+1).dev0)
-                Compatible => {
-                    if version.0.release.len() < 2 {
-                        bail!("~= operator requires a version with two segments (X.Y)");
-                    }
-                    let mut new_max = Version(pep440::Version {
-                        epoch: version.0.epoch,
-                        release: version.0.release.clone(),
-                        pre: None,
-                        post: None,
-                        dev: Some(0),
-                        local: vec![],
-                    });
-                    // Unwraps here are safe because we confirmed that the vector has at
-                    // least 2 elements above.
-                    new_max.0.release.pop().unwrap();
-                    *new_max.0.release.last_mut().unwrap() += 1;
-                    vec![version..new_max]
+                low..VERSION_INFINITY.clone()
+            }
+            // "The exclusive ordered comparison <V MUST NOT allow a pre-release of
+            // the specified version unless the specified version is itself a
+            // pre-release."
+            StrictlyLessThan => {
+                if (&version.0.pre, &version.0.dev) == (&None, &None) {
+                    let mut new_max = version.clone();
+                    new_max.0.dev = Some(0);
+                    new_max.0.post = None;
+                    new_max.0.local = vec![];
+                    VERSION_ZERO.clone()..new_max
+                } else {
+                    // Otherwise, some kind of pre-release
+                    VERSION_ZERO.clone()..version.clone()
                 }
             }
+            // ~= X.Y.suffixes is the same as >= X.Y.suffixes && == X.*
+            // So it's a half-open range:
+            //   [X.Y.suffixes, (X+1).dev0)
+            Compatible => version.clone()..version.compatible_upper_bound()?,
+            ArbitraryEqual => bail!("'===' can't be evaluated on parsed versions"),
         })
     }
+
+    /// Checks "lhs <op> rhs" for two concrete versions, e.g. for "lhs >= rhs" call
+    /// `GreaterThanEqual.matches(lhs, rhs)`. Same semantics as `to_ranges`, including
+    /// the special cases for pre/post/local versions, but without building the ranges.
+    /// Fails for '===' (which compares raw strings), and for combinations that
+    /// `to_ranges` rejects, like "< V+local".
+    pub fn matches(&self, lhs: &Version, rhs: &Version) -> Result<bool> {
+        let range = self.exact_range(rhs)?;
+        Ok(range.contains(lhs) != (self == &CompareOp::NotEqual))
+    }
 }
 
 #[cfg(test)]
@@ -277,13 +789,801 @@ mod test {
         let examples: Vec<(String, String)> =
             from_commented_json(include_str!("test-data/successful-specifiers.txt"));
 
+        // Like packaging's test suite, these tables ignore the pre-release exclusion
+        // rule, which has its own test.
         for (version_str, spec_str) in examples {
             println!("Matching {:?} against {:?}", version_str, spec_str);
             let version: Version = version_str.try_into().unwrap();
             let specs: Specifiers = spec_str.try_into().unwrap();
             println!("{:?}", specs.0[0].to_ranges());
-            assert!(specs.satisfied_by(&version).unwrap());
+            assert!(specs.satisfied_by_with_prereleases(&version, true).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_compare_op_roundtrip() {
+        for op_str in ["<=", "<", "!=", "==", ">=", ">", "~=", "==="] {
+            let op = CompareOp::from_str(op_str).unwrap();
+            assert_eq!(op.to_string(), op_str);
+        }
+        assert_eq!(
+            CompareOp::from_str("===").unwrap(),
+            CompareOp::ArbitraryEqual
+        );
+
+        let err = CompareOp::from_str("=>").unwrap_err();
+        assert!(err.to_string().contains("\"=>\""));
+        assert!(CompareOp::from_str("").is_err());
+    }
+
+    #[test]
+    fn test_arbitrary_equal() {
+        fn check(version_str: &str, spec_str: &str) -> bool {
+            let version: Version = version_str.try_into().unwrap();
+            let specs: Specifiers = spec_str.try_into().unwrap();
+            assert_eq!(specs.0[0].op(), CompareOp::ArbitraryEqual);
+            specs.satisfied_by(&version).unwrap()
+        }
+
+        assert!(check("1.0", "===1.0"));
+        assert!(check("1.0", "=== 1.0"));
+        // no normalization: 1.0 and 1.0.0 are different strings
+        assert!(!check("1.0", "===1.0.0"));
+        assert!(check("1.0.0", "===1.0.0"));
+        assert!(check("1.0+ubuntu1", "===1.0+ubuntu1"));
+        assert!(check("1.0+UBUNTU1", "===1.0+ubuntu1"));
+        assert!(!check("1.0+ubuntu1", "===1.0"));
+        assert!(!check("1.0", "===1.0+ubuntu1"));
+
+        let specs: Specifiers = "===1.0+ubuntu1".try_into().unwrap();
+        assert_eq!(specs.to_string(), "===1.0+ubuntu1");
+        assert!(specs.0[0].to_ranges().is_err());
+    }
+
+    #[test]
+    fn test_prerelease_exclusion() {
+        fn check(version_str: &str, spec_str: &str) -> (bool, bool) {
+            let version: Version = version_str.try_into().unwrap();
+            let specs: Specifiers = spec_str.try_into().unwrap();
+            (
+                specs.satisfied_by(&version).unwrap(),
+                specs.satisfied_by_with_prereleases(&version, true).unwrap(),
+            )
+        }
+
+        assert_eq!(check("2.0b1", ">=1.0"), (false, true));
+        assert_eq!(check("2.0", ">=1.0"), (true, true));
+        // post-releases aren't pre-releases
+        assert_eq!(check("1.0.post1", ">=1.0"), (true, true));
+        // dev releases are
+        assert_eq!(check("1.1.dev1", ">=1.0"), (false, true));
+        assert_eq!(check("1.0.post1.dev1", ">=1.0"), (false, true));
+        assert!(!Specifiers::default()
+            .satisfied_by(&"2.0b1".try_into().unwrap())
+            .unwrap());
+
+        // mentioning a pre-release opts in
+        assert_eq!(check("2.0b1", ">=1.0b1"), (true, true));
+        assert_eq!(check("2.0b1", ">=1.0.dev0"), (true, true));
+        assert_eq!(check("2.0b1", "==2.0b1"), (true, true));
+        assert_eq!(check("2.0b2", "~=2.0b1"), (true, true));
+        assert_eq!(check("2.0b2", "<=2.0b3"), (true, true));
+        assert_eq!(check("1.0a2", "===1.0a2"), (true, true));
+        assert_eq!(check("2.0b1", ">=1.0, <=3.0rc1"), (true, true));
+        // ...but excluding one doesn't
+        assert_eq!(check("2.0b2", "!=2.0b1"), (false, true));
+        assert_eq!(check("1.0b1", "<2.0b1"), (false, true));
+        assert_eq!(check("2.0b2", ">2.0b1"), (false, true));
+
+        // explicitly disallowing overrides the specifiers
+        let specs: Specifiers = "==2.0b1".try_into().unwrap();
+        assert!(!specs
+            .satisfied_by_with_prereleases(&"2.0b1".try_into().unwrap(), false)
+            .unwrap());
+    }
+
+    #[test]
+    fn test_wildcard_epochs() {
+        fn ranges(spec: &str) -> Vec<(String, String)> {
+            let specs: Specifiers = spec.try_into().unwrap();
+            specs.0[0]
+                .to_ranges()
+                .unwrap()
+                .into_iter()
+                .map(|r| (r.start.to_string(), r.end.to_string()))
+                .collect()
+        }
+        fn s(a: &str, b: &str) -> (String, String) {
+            (a.into(), b.into())
+        }
+
+        assert_eq!(ranges("==2!1.*"), vec![s("2!1.dev0", "2!2.dev0")]);
+        assert_eq!(ranges("==1!1.0.*"), vec![s("1!1.0.dev0", "1!1.1.dev0")]);
+        assert_eq!(
+            ranges("!=2!3.*"),
+            vec![
+                s(&VERSION_ZERO.to_string(), "2!3.dev0"),
+                s("2!4.dev0", &VERSION_INFINITY.to_string()),
+            ]
+        );
+
+        let specs: Specifiers = "==1!1.0.*".try_into().unwrap();
+        assert!(specs.satisfied_by(&"1!1.0.5".try_into().unwrap()).unwrap());
+        assert!(!specs.satisfied_by(&"1.0.5".try_into().unwrap()).unwrap());
+        assert!(!specs.satisfied_by(&"2!1.0".try_into().unwrap()).unwrap());
+
+        let specs: Specifiers = "!=2!3.*".try_into().unwrap();
+        assert!(specs.satisfied_by(&"3.5".try_into().unwrap()).unwrap());
+        assert!(!specs.satisfied_by(&"2!3.5".try_into().unwrap()).unwrap());
+        assert!(specs.satisfied_by(&"2!4.0".try_into().unwrap()).unwrap());
+    }
+
+    #[test]
+    fn test_highest_match() {
+        fn versions(vs: &[&str]) -> Vec<Version> {
+            vs.iter().map(|v| (*v).try_into().unwrap()).collect()
+        }
+        fn highest(spec: &str, vs: &[Version]) -> Option<String> {
+            let specs: Specifiers = spec.try_into().unwrap();
+            specs.highest_match(vs).unwrap().map(|v| v.to_string())
         }
+
+        let vs = versions(&["1.0", "2.0", "1.5", "3.0.dev1", "2.0.post1", "0.9"]);
+        assert_eq!(highest("<2", &vs).as_deref(), Some("1.5"));
+        assert_eq!(highest(">=1,<3", &vs).as_deref(), Some("2.0.post1"));
+        assert_eq!(highest("==2.0", &vs).as_deref(), Some("2.0"));
+        assert_eq!(highest(">5", &vs), None);
+        assert_eq!(highest(">=1", &[]), None);
+
+        // only pre-releases match
+        let vs = versions(&["1.0", "2.0a1", "2.0b2", "2.0a3"]);
+        assert_eq!(highest(">=2.0a1", &vs).as_deref(), Some("2.0b2"));
+
+        // (only lenient parsing lets this through)
+        let bad =
+            Specifiers::parse(">=1.0+local", SpecifierStrictness::Lenient).unwrap();
+        assert!(bad.highest_match(&vs).is_err());
+    }
+
+    #[test]
+    fn test_intersect() {
+        fn specs(s: &str) -> Specifiers {
+            s.try_into().unwrap()
+        }
+
+        let combined = specs(">=1.2").intersect(&specs("<2.0,!=1.5"));
+        assert_eq!(combined, specs(">=1.2,<2.0,!=1.5"));
+        assert!(combined.is_satisfiable().unwrap());
+        assert!(combined.satisfied_by(&"1.7".try_into().unwrap()).unwrap());
+        assert!(!combined.satisfied_by(&"1.5".try_into().unwrap()).unwrap());
+
+        // duplicates are dropped
+        assert_eq!(specs(">=1,<2").intersect(&specs("<2")), specs(">=1,<2"));
+
+        assert!(!specs(">=2.0")
+            .intersect(&specs("<1.0"))
+            .is_satisfiable()
+            .unwrap());
+        assert!(!specs(">=2.0,<2.0").is_satisfiable().unwrap());
+        assert!(!specs("==1.5,!=1.5").is_satisfiable().unwrap());
+        assert!(!specs("==1.*,==2.*").is_satisfiable().unwrap());
+        assert!(specs("!=1.*,!=2.*").is_satisfiable().unwrap());
+        assert!(specs(">=1.0,<=1.0").is_satisfiable().unwrap());
+        assert!(Specifiers::default().is_satisfiable().unwrap());
+
+        assert!(specs("===1.0").is_satisfiable().is_err());
+    }
+
+    #[test]
+    fn test_specifier_strictness() {
+        use SpecifierStrictness::*;
+
+        for lenient_only in [">=1.0,", ">=1.0, <2 , ", ">=2.7.*", "~=1", "<1.0+local"] {
+            println!("{:?}", lenient_only);
+            assert!(Specifiers::parse(lenient_only, Strict).is_err());
+            assert!(Specifiers::try_from(lenient_only).is_err());
+            assert!(Specifiers::parse(lenient_only, Lenient).is_ok());
+        }
+        assert_eq!(
+            Specifiers::parse(">=1.0,", Lenient).unwrap(),
+            Specifiers::parse(">=1.0", Strict).unwrap()
+        );
+        // lenient still checks semantics, just later
+        let specs = Specifiers::parse(">=2.7.*", Lenient).unwrap();
+        assert!(specs.satisfied_by(&"3.0".try_into().unwrap()).is_err());
+
+        // === takes anything, even in strict mode
+        assert!(Specifiers::parse("===foobar", Strict).is_ok());
+        // and both reject things that aren't specifiers at all
+        for bad in ["2.0", ">=1.0,,", ","] {
+            assert!(Specifiers::parse(bad, Lenient).is_err());
+        }
+    }
+
+    #[test]
+    fn test_vocab_errors() {
+        fn vocab_err<T: std::fmt::Debug>(r: Result<T>) -> VocabError {
+            r.unwrap_err().downcast_ref::<VocabError>().unwrap().clone()
+        }
+
+        assert_eq!(
+            vocab_err(Version::try_from("1.0-garbage")),
+            VocabError::InvalidVersion {
+                input: "1.0-garbage".into()
+            }
+        );
+        assert_eq!(
+            vocab_err(CompareOp::try_from("=~")),
+            VocabError::UnknownOperator { op: "=~".into() }
+        );
+        // syntax errors report the whole input...
+        assert_eq!(
+            vocab_err(Specifiers::try_from(">=1.0, !!!")),
+            VocabError::InvalidSpecifier {
+                input: ">=1.0, !!!".into()
+            }
+        );
+        // ...and strict checks report just the offending specifier
+        assert_eq!(
+            vocab_err(Specifiers::try_from(">=1.0, ~=2")),
+            VocabError::InvalidSpecifier {
+                input: "~=2".into()
+            }
+        );
+        // the original cause is still in the chain
+        let err = Specifiers::try_from("~=2").unwrap_err();
+        assert!(format!("{:#}", err).contains("at least two release segments"));
+    }
+
+    #[test]
+    fn test_explain() {
+        let v = |s: &str| -> Version { s.try_into().unwrap() };
+        let specs = |s: &str| -> Specifiers { s.try_into().unwrap() };
+        assert_eq!(
+            specs(">=1.2,<2.0").explain(&v("2.5")),
+            ">=1.2 ✓; <2.0 ✗ (2.5 is too high)"
+        );
+        assert_eq!(
+            specs(">=1.2,<2.0").explain(&v("1.0")),
+            ">=1.2 ✗ (1.0 is too low); <2.0 ✓"
+        );
+        assert_eq!(
+            specs("!=1.5.*").explain(&v("1.5.3")),
+            "!=1.5.* ✗ (1.5.3 is excluded)"
+        );
+        assert_eq!(
+            specs("===1.0").explain(&v("1.0.0")),
+            r#"===1.0 ✗ (1.0.0 isn't literally "1.0")"#
+        );
+        assert_eq!(
+            specs(">=1.0").explain(&v("2.0b1")),
+            ">=1.0 ✓; 2.0b1 is a pre-release, and no specifier mentions one"
+        );
+        let lenient =
+            Specifiers::parse(">=2.7.*", SpecifierStrictness::Lenient).unwrap();
+        assert!(lenient
+            .explain(&v("3.0"))
+            .starts_with(">=2.7.* ✗ (invalid: "));
+        assert_eq!(
+            Specifiers::default().explain(&v("1.0")),
+            "no specifiers; any version matches"
+        );
+    }
+
+    #[test]
+    fn test_is_exact_pin() {
+        let pin = |s: &str| {
+            Specifiers::try_from(s)
+                .unwrap()
+                .is_exact_pin()
+                .map(|v| v.to_string())
+        };
+        assert_eq!(pin("==1.2.3"), Some("1.2.3".into()));
+        assert_eq!(pin("== 1.0+local"), Some("1.0+local".into()));
+        assert_eq!(pin("==1.2.*"), None);
+        assert_eq!(pin(">=1,<2"), None);
+        assert_eq!(pin("===1.0"), None);
+        assert_eq!(pin("==1.0,==1.0"), None);
+        assert_eq!(Specifiers::default().is_exact_pin(), None);
+    }
+
+    #[test]
+    fn test_equal_local_versions() {
+        let matches = |spec: &str, version: &str| {
+            let specs: Specifiers = spec.try_into().unwrap();
+            specs.satisfied_by(&version.try_into().unwrap()).unwrap()
+        };
+        // no local label in the specifier: any local label matches
+        assert!(matches("==1.0", "1.0"));
+        assert!(matches("==1.0", "1.0+abc"));
+        assert!(matches("==1.0", "1.0+abc.7"));
+        assert!(!matches("!=1.0", "1.0+abc"));
+        // local label in the specifier: exact match only
+        assert!(matches("==1.0+abc", "1.0+abc"));
+        assert!(matches("==1.0+abc", "1.0.0+ABC"));
+        for other in [
+            "1.0",
+            "1.0+abc.0",
+            "1.0+abc.a",
+            "1.0+abd",
+            "1.0+ab",
+            "1.0.post0",
+        ] {
+            assert!(!matches("==1.0+abc", other), "{other}");
+            assert!(matches("!=1.0+abc", other), "{other}");
+        }
+        assert!(!matches("!=1.0+abc", "1.0+abc"));
+    }
+
+    #[test]
+    fn test_compare_op_matches() {
+        use crate::vocab::arbitrary::arbitrary_version;
+        use CompareOp::*;
+
+        fn v(s: &str) -> Version {
+            s.try_into().unwrap()
+        }
+        assert!(GreaterThanEqual.matches(&v("1.1"), &v("1.0")).unwrap());
+        assert!(!StrictlyGreaterThan
+            .matches(&v("1.0.post1"), &v("1.0"))
+            .unwrap());
+        assert!(Equal.matches(&v("1.0+abc"), &v("1.0")).unwrap());
+        assert!(!NotEqual.matches(&v("1.0+abc"), &v("1.0")).unwrap());
+        assert!(!StrictlyLessThan.matches(&v("1.0rc1"), &v("1.0")).unwrap());
+        assert!(Compatible.matches(&v("1.9"), &v("1.2")).unwrap());
+        assert!(ArbitraryEqual.matches(&v("1.0"), &v("1.0")).is_err());
+        assert!(StrictlyLessThan.matches(&v("1.0"), &v("1.0+abc")).is_err());
+        assert!(Compatible.matches(&v("1.0"), &v("1")).is_err());
+
+        // cross-check against the range-based evaluation
+        let ops = [
+            LessThanEqual,
+            StrictlyLessThan,
+            NotEqual,
+            Equal,
+            GreaterThanEqual,
+            StrictlyGreaterThan,
+            Compatible,
+        ];
+        let versions: Vec<Version> = (0..60)
+            .map(|seed| arbitrary_version(&fastrand::Rng::with_seed(seed)))
+            .collect();
+        for op in ops {
+            for rhs in &versions {
+                let ranges = op.to_ranges(&rhs.to_string());
+                for lhs in &versions {
+                    match &ranges {
+                        Ok(ranges) => assert_eq!(
+                            op.matches(lhs, rhs).unwrap(),
+                            ranges.iter().any(|r| r.contains(lhs)),
+                            "{lhs} {op} {rhs}"
+                        ),
+                        Err(_) => assert!(op.matches(lhs, rhs).is_err()),
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_compatible_needs_two_segments() {
+        // strict parsing catches it right away
+        let err = Specifiers::try_from("~=1").unwrap_err();
+        assert!(format!("{:#}", err)
+            .contains("~= requires at least two release segments, got '1'"));
+        let err = CompareOp::Compatible.to_ranges("1a1").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "~= requires at least two release segments, got '1a1'"
+        );
+        assert!(Specifiers::try_from("~=1.0").is_ok());
+    }
+
+    #[test]
+    fn test_strictly_greater_than() {
+        let matches = |spec: &str, version: &str| {
+            let specs: Specifiers = spec.try_into().unwrap();
+            specs
+                .satisfied_by_with_prereleases(&version.try_into().unwrap(), true)
+                .unwrap()
+        };
+        for excluded in [
+            "1.0",
+            "1.0.0",
+            "1.0+abc",
+            "1.0.post1",
+            "1.0.post999999999",
+            "1.0.post4294967295",
+            "1.0.post4294967295.dev4294967295+zzz",
+            "0.9",
+            "1.0.dev1",
+            "1.0rc1",
+        ] {
+            assert!(!matches(">1.0", excluded), "{excluded}");
+        }
+        for included in ["1.0.0.1", "1.0.0.0.0.0.1", "1.0.1.dev0", "1.1", "1!0.1"] {
+            assert!(matches(">1.0", included), "{included}");
+        }
+
+        // same deal for pre-releases
+        assert!(!matches(">1.0a1", "1.0a1.post4294967295"));
+        assert!(matches(">1.0a1", "1.0a2.dev0"));
+        assert!(matches(">1.0a1", "1.0"));
+        // post-releases of post-releases are fine though
+        assert!(!matches(">1.0.post1", "1.0.post1+abc"));
+        assert!(matches(">1.0.post1", "1.0.post2"));
+    }
+
+    #[test]
+    fn test_union() {
+        fn v(s: &str) -> Version {
+            s.try_into().unwrap()
+        }
+        let union = |a: &str, b: &str| {
+            let a: Specifiers = a.try_into().unwrap();
+            let b: Specifiers = b.try_into().unwrap();
+            a.union(&b).unwrap()
+        };
+        // adjacent
+        assert_eq!(union("==1.*", "==2.*"), vec![v("1.dev0")..v("3.dev0")]);
+        // not quite adjacent: "<2" excludes 2's pre-releases, and ">=2" doesn't
+        // include them either
+        assert_eq!(
+            union(">=1,<2", ">=2,<3"),
+            vec![v("1")..v("2.dev0"), v("2")..v("3.dev0")]
+        );
+        // overlapping, in either order
+        assert_eq!(union(">=1.5,<3", ">=1,<2"), vec![v("1")..v("3.dev0")]);
+        // one contains the other
+        assert_eq!(
+            union(">=1", "==1.5"),
+            vec![v("1")..VERSION_INFINITY.clone()]
+        );
+        // disjoint
+        assert_eq!(
+            union(">=2,<3", ">=1,<1.5"),
+            vec![v("1")..v("1.5.dev0"), v("2")..v("3.dev0")]
+        );
+        // unsatisfiable sides contribute nothing
+        assert_eq!(union(">=2,<1", ">=1,<2"), vec![v("1")..v("2.dev0")]);
+        assert_eq!(union(">=2,<1", ">3,<3"), vec![]);
+
+        let err = Specifiers::try_from("===1.0")
+            .unwrap()
+            .union(&Specifiers::default());
+        assert!(err.is_err());
+    }
+
+    #[test]
+    fn test_for_display() {
+        let canonical = |input: &str| {
+            let specs: Specifiers = input.try_into().unwrap();
+            let sorted = specs.for_display();
+            assert!(sorted.semantic_eq(&specs).unwrap_or(true));
+            sorted.to_string()
+        };
+        assert_eq!(canonical("<2,>=1"), ">=1,<2");
+        assert_eq!(canonical(">=1,<2"), ">=1,<2");
+        assert_eq!(canonical("!=1.5,<2,>=1"), ">=1,!=1.5,<2");
+        assert_eq!(canonical("<=2.0,>=2.0"), ">=2.0,<=2.0");
+        assert_eq!(canonical("<3,==2.*,>1"), ">1,==2.*,<3");
+        assert_eq!(canonical("===foobar,<2,~=1.4"), "~=1.4,<2,===foobar");
+        // 1.0 and 1 are the same version, so the operator decides
+        assert_eq!(canonical("<1.0,>1"), ">1,<1.0");
+        assert_eq!(Specifiers::empty().for_display(), Specifiers::empty());
+    }
+
+    #[test]
+    fn test_semantic_eq() {
+        let eq = |a: &str, b: &str| {
+            let a: Specifiers = a.try_into().unwrap();
+            let b: Specifiers = b.try_into().unwrap();
+            a.semantic_eq(&b).unwrap()
+        };
+        assert!(eq(">=1.0,<2.0", "<2.0,>=1.0"));
+        assert!(eq(">=1.0,<2.0", ">=1,<2"));
+        assert!(eq(">=1.0,<2.0", ">=1.0,<2.0,>=0.5"));
+        assert!(eq("~=1.4", ">=1.4,==1.*"));
+        assert!(eq("==1.5", "==1.5.0"));
+        // every unsatisfiable set is the same
+        assert!(eq(">=2,<1", "<0.dev0"));
+
+        assert!(!eq(">=1.0,<2.0", ">=1.0,<=2.0"));
+        assert!(!eq(">=1.0,<2.0", ">=1.0,<2.0,!=1.5"));
+        assert!(!eq("~=1.4", "~=1.4.0"));
+        assert!(!eq(">=1.0", ">1.0"));
+
+        let err = Specifiers::try_from("===1.0")
+            .unwrap()
+            .semantic_eq(&Specifiers::empty());
+        assert!(err.is_err());
+    }
+
+    #[test]
+    fn test_is_subset_of() {
+        let subset = |a: &str, b: &str| {
+            let a: Specifiers = a.try_into().unwrap();
+            let b: Specifiers = b.try_into().unwrap();
+            a.is_subset_of(&b).unwrap()
+        };
+        assert!(subset(">=1.5,<2", ">=1"));
+        assert!(!subset(">=1", ">=1.5,<2"));
+        // every set is a subset of itself, and of no constraint at all
+        assert!(subset(">=1.5,<2", ">=1.5,<2"));
+        assert!(subset(">=1.5,<2", "!=3.0"));
+        assert!(Specifiers::try_from(">=1")
+            .unwrap()
+            .is_subset_of(&Specifiers::default())
+            .unwrap());
+        assert!(subset("==1.7", ">=1.5,<2"));
+        assert!(subset("~=1.5", ">=1.5,<2"));
+        // overlapping but not contained
+        assert!(!subset(">=1.5,<2", ">=1.8"));
+        assert!(!subset(">=1,<3", ">=1,!=2.0"));
+        assert!(subset(">=1,<3,!=2.*", "!=2.5"));
+        // the empty set is a subset of everything
+        assert!(subset(">=2,<1", "==5"));
+
+        let err = Specifiers::try_from("===1.0")
+            .unwrap()
+            .is_subset_of(&Specifiers::default());
+        assert!(err.is_err());
+    }
+
+    #[test]
+    fn test_requires_python() {
+        let rp: RequiresPython = ">=3.7,<4".try_into().unwrap();
+        let contains = |v: &str| rp.contains(&v.try_into().unwrap()).unwrap();
+        assert!(contains("3.7"));
+        assert!(contains("3.7.0"));
+        assert!(contains("3.11.4"));
+        assert!(contains("3.99"));
+        assert!(!contains("3.6.15"));
+        assert!(!contains("2.7.18"));
+        assert!(!contains("4.0"));
+        // pre-release interpreters are fine
+        assert!(contains("3.12.0b1"));
+        assert!(!contains("4.0.0a1"));
+        // ...as long as they're in range
+        assert!(!contains("3.7.0rc1"));
+
+        // same syntax leniency as other metadata
+        let rp: RequiresPython = ">= 3.7, ".try_into().unwrap();
+        assert_eq!(rp.to_string(), ">=3.7");
+        assert_eq!(*rp, Specifiers::try_from(">=3.7").unwrap());
+        assert!(RequiresPython::default()
+            .contains(&"2.7".try_into().unwrap())
+            .unwrap());
+
+        for bad in ["===3.7", "==3.7+local", ">=3.7.*", "~=3", ">=three"] {
+            println!("{:?}", bad);
+            assert!(RequiresPython::try_from(bad).is_err());
+        }
+    }
+
+    #[test]
+    fn test_empty_and_push() {
+        let mut specs = Specifiers::empty();
+        assert_eq!(specs, Specifiers::default());
+        for v in ["0.dev0", "1.0", "2.0rc1", "3!4.5.post6+local"] {
+            let v: Version = v.try_into().unwrap();
+            assert!(specs.satisfied_by_with_prereleases(&v, true).unwrap());
+        }
+        assert!(specs.satisfied_by(&"1.0".try_into().unwrap()).unwrap());
+        assert_eq!(
+            specs.to_ranges().unwrap(),
+            vec![VERSION_ZERO.clone()..VERSION_INFINITY.clone()]
+        );
+
+        specs.push(Specifier::new(CompareOp::GreaterThanEqual, "1.0").unwrap());
+        specs.push(Specifier::new(CompareOp::StrictlyLessThan, "2.0").unwrap());
+        assert_eq!(specs, Specifiers::try_from(">=1.0,<2.0").unwrap());
+        assert!(!specs.satisfied_by(&"2.0".try_into().unwrap()).unwrap());
+    }
+
+    #[test]
+    fn test_specifiers_iteration() {
+        let specs: Specifiers = ">=1.0, !=1.5, <2".try_into().unwrap();
+        assert_eq!(specs.len(), 3);
+        assert!(!specs.is_empty());
+        assert!(Specifiers::empty().is_empty());
+
+        let ops: Vec<CompareOp> = specs.iter().map(|s| s.op()).collect();
+        assert_eq!(
+            ops,
+            [
+                CompareOp::GreaterThanEqual,
+                CompareOp::NotEqual,
+                CompareOp::StrictlyLessThan
+            ]
+        );
+        let mut values = Vec::new();
+        for spec in &specs {
+            values.push(spec.value());
+        }
+        assert_eq!(values, ["1.0", "1.5", "2"]);
+        let owned: Vec<String> = specs.into_iter().map(|s| s.to_string()).collect();
+        assert_eq!(owned, [">=1.0", "!=1.5", "<2"]);
+    }
+
+    #[test]
+    fn test_filter() {
+        let versions: Vec<Version> = ["2.0", "1.0", "1.5", "1.5.1", "3.0", "1.9b1"]
+            .into_iter()
+            .map(|v| v.try_into().unwrap())
+            .collect();
+        let specs: Specifiers = ">=1.5,<3,!=1.5.1".try_into().unwrap();
+        let got = specs.filter(&versions).unwrap();
+        let got_strs: Vec<String> = got.iter().map(|v| v.to_string()).collect();
+        // 1.9b1 is excluded because it's a pre-release
+        assert_eq!(got_strs, vec!["2.0", "1.5"]);
+        for version in &versions {
+            assert_eq!(got.contains(&version), specs.satisfied_by(version).unwrap());
+        }
+
+        let specs: Specifiers = "===1.5.1".try_into().unwrap();
+        assert_eq!(specs.filter(&versions).unwrap(), vec![&versions[3]]);
+
+        assert_eq!(Specifiers::default().filter(&versions).unwrap().len(), 5);
+
+        // (only lenient parsing lets this through)
+        let bad =
+            Specifiers::parse(">=1.0+local", SpecifierStrictness::Lenient).unwrap();
+        assert!(bad.filter(&versions).is_err());
+    }
+
+    #[test]
+    fn test_specifier_new() {
+        use CompareOp::*;
+
+        let spec = Specifier::new(GreaterThanEqual, "1.0").unwrap();
+        assert_eq!(spec.to_string(), ">=1.0");
+        assert!(spec.satisfied_by(&"1.1".try_into().unwrap()).unwrap());
+        assert!(Specifier::new(Equal, "1.*").is_ok());
+        assert!(Specifier::new(NotEqual, "1.0+local").is_ok());
+        assert!(Specifier::new(ArbitraryEqual, "anything goes").is_ok());
+
+        let err = Specifier::new(GreaterThanEqual, "1.*").unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<VocabError>(),
+            Some(&VocabError::InvalidSpecifier {
+                input: ">=1.*".into()
+            })
+        );
+        for (op, bad) in [
+            (Compatible, "1"),
+            (StrictlyLessThan, "1.0+local"),
+            (Equal, "1.*.0"),
+            (Equal, "not a version"),
+        ] {
+            assert!(Specifier::new(op, bad).is_err(), "{op}{bad}");
+            // still available for when you want lenient behavior
+            let unchecked = Specifier::new_unchecked(op, bad.into());
+            assert!(unchecked.satisfied_by(&"1.0".try_into().unwrap()).is_err());
+        }
+    }
+
+    #[test]
+    fn test_satisfied_by_exact_pin_fast_path() {
+        use crate::vocab::arbitrary::arbitrary_version;
+
+        let mut versions: Vec<Version> = (0..80)
+            .map(|seed| arbitrary_version(&fastrand::Rng::with_seed(seed)))
+            .collect();
+        for extra in ["1.0", "1.0.0", "1.0+abc", "1.0+abc.1", "1.0.post1", "1!1.0"] {
+            versions.push(extra.try_into().unwrap());
+        }
+        for pin in &versions {
+            let fast = Specifier::new_unchecked(CompareOp::Equal, pin.to_string());
+            let slow = Specifier::new_unchecked(CompareOp::Equal, pin.to_string());
+            let ranges = slow.ranges().unwrap();
+            // several passes over the same Specifier, so after the first candidate
+            // everything goes through the cached version
+            for _ in 0..3 {
+                for candidate in &versions {
+                    assert_eq!(
+                        fast.satisfied_by(candidate).unwrap(),
+                        ranges.iter().any(|r| r.contains(candidate)),
+                        "{candidate} == {pin}"
+                    );
+                    assert!(fast.version.get().is_some());
+                }
+            }
+            // the fast path doesn't need the ranges
+            assert!(fast.ranges.get().is_none());
+        }
+
+        let pin = |s: &str| Specifier::new(CompareOp::Equal, s).unwrap();
+        let v = |s: &str| -> Version { s.try_into().unwrap() };
+        assert!(pin("1.0").satisfied_by(&v("1.0.0+abc")).unwrap());
+        assert!(pin("1.0+abc").satisfied_by(&v("1.0.0+abc")).unwrap());
+        assert!(!pin("1.0+abc").satisfied_by(&v("1.0")).unwrap());
+        assert!(!pin("1.0+abc").satisfied_by(&v("1.0+abc.1")).unwrap());
+        assert!(!pin("1.0").satisfied_by(&v("1.0.post1")).unwrap());
+        // invalid values still error out
+        let bad = Specifier::new_unchecked(CompareOp::Equal, "1.0+".into());
+        assert!(bad.satisfied_by(&v("1.0")).is_err());
+    }
+
+    #[test]
+    fn test_specifier_range_cache() {
+        let spec = Specifier::new_unchecked(CompareOp::GreaterThanEqual, "1.0".into());
+        assert!(spec.ranges.get().is_none());
+        assert!(spec.satisfied_by(&"1.1".try_into().unwrap()).unwrap());
+        assert!(spec.ranges.get().is_some());
+        // clones share the cached value, and it doesn't affect equality
+        let cloned = spec.clone();
+        assert!(cloned.ranges.get().is_some());
+        assert_eq!(
+            cloned,
+            Specifier::new(CompareOp::GreaterThanEqual, "1.0").unwrap()
+        );
+
+        let bad =
+            Specifier::new_unchecked(CompareOp::GreaterThanEqual, "1.0+local".into());
+        let version = "1.0".try_into().unwrap();
+        for _ in 0..2 {
+            let err = bad.satisfied_by(&version).unwrap_err();
+            assert!(err.to_string().contains("+local"));
+        }
+        assert!(bad.ranges.get().is_none());
+
+        // Specifiers::satisfied_by checks for pre-releases on every call; that's
+        // parsed once too
+        let specs: Specifiers = "<3,>=2.0b1".try_into().unwrap();
+        assert!(specs.0[1].version.get().is_none());
+        for candidate in ["2.0b2", "2.5", "3.0a1"] {
+            specs.satisfied_by(&candidate.try_into().unwrap()).unwrap();
+        }
+        assert!(specs.0[1].version.get().is_some());
+        assert!(!specs.0[0].mentions_prerelease());
+        assert!(specs.0[1].mentions_prerelease());
+    }
+
+    #[test]
+    fn test_specifiers_display_roundtrip() {
+        let examples: Vec<(String, String)> =
+            from_commented_json(include_str!("test-data/successful-specifiers.txt"));
+
+        for (_, spec_str) in examples {
+            let specs: Specifiers = spec_str.as_str().try_into().unwrap();
+            let displayed = specs.to_string();
+            println!("{:?} -> {:?}", spec_str, displayed);
+            assert!(!displayed.contains(' '));
+            let reparsed: Specifiers = displayed.as_str().try_into().unwrap();
+            assert_eq!(specs, reparsed);
+        }
+
+        let specs: Specifiers = ">= 1.2, < 2.0".try_into().unwrap();
+        assert_eq!(specs.to_string(), ">=1.2,<2.0");
+    }
+
+    #[test]
+    fn test_arbitrary_specifiers_roundtrip() {
+        use crate::vocab::arbitrary::arbitrary_specifiers;
+
+        for seed in 0..1000 {
+            let specs = arbitrary_specifiers(&fastrand::Rng::with_seed(seed));
+            let roundtrip: Specifiers = specs.to_string().try_into().unwrap();
+            assert_eq!(roundtrip, specs);
+            // and they're all valid, not just syntactically
+            for spec in &specs.0 {
+                spec.to_ranges().unwrap();
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_specifiers_serde() {
+        let specs: Specifiers = ">= 1.0, < 2".try_into().unwrap();
+        let json = serde_json::to_string(&specs).unwrap();
+        assert_eq!(json, r#"">=1.0,<2""#);
+        let roundtrip: Specifiers = serde_json::from_str(&json).unwrap();
+        assert_eq!(roundtrip, specs);
+
+        let bad: serde_json::Result<Specifiers> = serde_json::from_str(r#""=>1.0""#);
+        assert!(bad.is_err());
     }
 
     #[test]
@@ -296,7 +1596,7 @@ mod test {
             let version: Version = version_str.try_into().unwrap();
             let specs: Specifiers = spec_str.try_into().unwrap();
             println!("{:?}", specs.0[0].to_ranges());
-            assert!(!specs.satisfied_by(&version).unwrap());
+            assert!(!specs.satisfied_by_with_prereleases(&version, true).unwrap());
         }
     }
 }
@@ -3,24 +3,37 @@ use crate::prelude::*;
 // We lean on the 'pep440' crate for the heavy lifting part of representing versions,
 // but wrap it in our own type so that we can e.g. make it play nice with pubgrub.
 
-#[derive(
-    Clone,
-    PartialEq,
-    Eq,
-    PartialOrd,
-    Ord,
-    Debug,
-    Hash,
-    SerializeDisplay,
-    DeserializeFromStr,
-)]
+/// The pieces of a version that the 'pep440' crate defines for us. A local segment is
+/// one dot-separated part of the "+local" suffix; see `Version::local_segments` for
+/// how they sort.
+pub use pep440::{LocalVersion as LocalSegment, PreRelease};
+
+/// A PEP 440 version.
+///
+/// Ordering follows PEP 440: compare epoch, then release (ignoring trailing zeros, so
+/// "1.0" == "1.0.0"), then pre-release, post-release, dev-release, and finally the
+/// local segment. Dev-releases sort before everything else with the same release, so
+/// e.g. 1.0.dev1 < 1.0a1 < 1.0 < 1.0+local < 1.0.post1.
+///
+/// With the `serde` feature (on by default), this serializes as its normalized string
+/// form, and deserializes by parsing that string (so invalid versions come out as
+/// regular serde errors). VERSION_INFINITY refuses to serialize, since it's not a real
+/// version.
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+#[cfg_attr(feature = "serde", derive(DeserializeFromStr))]
 pub struct Version(pub pep440::Version);
 
-pub static VERSION_ZERO: Lazy<Version> = Lazy::new(|| "0a0.dev0".try_into().unwrap());
+/// The smallest possible version, "0.dev0". We use it as the lower bound of ranges like
+/// "< 2" (and pubgrub uses it as `lowest()`). It's a perfectly valid version on its
+/// own, but if you see it anywhere else, it's almost certainly a range bound leaking
+/// out.
+pub static VERSION_ZERO: Lazy<Version> = Lazy::new(|| "0.dev0".try_into().unwrap());
 
+/// The upper bound for ranges like ">= 2". There is no largest PEP 440 version, so this
+/// is a made-up value that compares greater than every other version (that's special
+/// cased in `Ord`; the actual fields are just as large as possible). It displays as
+/// "∞" and can't be serialized, so it can't end up in a lock file by accident.
 pub static VERSION_INFINITY: Lazy<Version> = Lazy::new(|| {
-    // Technically there is no largest PEP 440 version. But this should be good
-    // enough that no-one will notice the difference...
     Version(pep440::Version {
         epoch: u32::MAX,
         release: vec![u32::MAX, u32::MAX, u32::MAX],
@@ -31,14 +44,291 @@ pub static VERSION_INFINITY: Lazy<Version> = Lazy::new(|| {
     })
 });
 
+// A trailing "final" tag, which setuptools-era tools treated as a no-op
+static LEGACY_FINAL: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?i)[-_.]?final$").unwrap());
+
+// Marks a version made by Version::after_post_releases. The parser never produces an
+// empty local segment, so this can't collide with a real version.
+fn after_post_releases_marker() -> Vec<pep440::LocalVersion> {
+    vec![pep440::LocalVersion::StringComponent(String::new())]
+}
+
 impl Version {
+    /// Builds a version out of its parts, e.g. epoch 2, release [1, 0], pre
+    /// `PreRelease::A(1)`, post 2, dev 3, and local `[StringComponent("abc")]` give
+    /// "2!1.0a1.post2.dev3+abc". Checks what the type system can't:
+    ///
+    /// - there has to be at least one release segment
+    /// - string local segments have to be non-empty and alphanumeric, and can't be all
+    ///   digits (those are `NumericComponent`s). They're lowercased, like the parser
+    ///   does.
+    ///
+    /// So the result always displays as a version that parses back to itself.
+    pub fn from_components(
+        epoch: u32,
+        release: Vec<u32>,
+        pre: Option<PreRelease>,
+        post: Option<u32>,
+        dev: Option<u32>,
+        local: Vec<LocalSegment>,
+    ) -> Result<Version> {
+        if release.is_empty() {
+            bail!("versions need at least one release segment");
+        }
+        let local = local
+            .into_iter()
+            .map(|segment| match segment {
+                LocalSegment::StringComponent(s) => {
+                    if s.is_empty() || !s.chars().all(|c| c.is_ascii_alphanumeric()) {
+                        bail!("invalid local version segment {:?}", s);
+                    }
+                    if s.chars().all(|c| c.is_ascii_digit()) {
+                        bail!(
+                            "local version segment {:?} is numeric; use \
+                             NumericComponent",
+                            s
+                        );
+                    }
+                    Ok(LocalSegment::StringComponent(s.to_ascii_lowercase()))
+                }
+                numeric => Ok(numeric),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Version(pep440::Version {
+            epoch,
+            release,
+            pre,
+            post,
+            dev,
+            local,
+        }))
+    }
+
+    /// True for VERSION_ZERO, VERSION_INFINITY, and the `after_post_releases` and
+    /// `local_successor` bounds, which only exist to mark the ends of version ranges.
+    pub fn is_sentinel(&self) -> bool {
+        self.is_infinity() || self.has_empty_local_segment() || self == &*VERSION_ZERO
+    }
+
+    /// Returns a made-up version that sorts after self, all of self's post-releases,
+    /// and their dev and local variants, but before everything else. So it's the
+    /// exclusive lower bound for "> V", which per PEP 440 shouldn't match those:
+    ///
+    ///   1.0 < 1.0+local < 1.0.post1 < 1.0.post4294967295 < [bound] < 1.0.0.1.dev0
+    ///
+    /// There's no real smallest version with that property, so this is special-cased
+    /// in `Ord`, like VERSION_INFINITY. It's a sentinel, so it can't be serialized.
+    pub fn after_post_releases(&self) -> Version {
+        Version(pep440::Version {
+            epoch: self.0.epoch,
+            release: self.0.release.clone(),
+            pre: self.0.pre.clone(),
+            post: None,
+            dev: None,
+            local: after_post_releases_marker(),
+        })
+    }
+
+    /// For a version with a local segment, returns the smallest version that's larger
+    /// than it: the same version with an extra empty segment tacked onto the local
+    /// part. Empty segments sort before any real one, and the parser never produces
+    /// them, so nothing real can fit in between. We use it as the end of the range for
+    /// "== V+local". Like `after_post_releases`, it's a sentinel.
+    ///
+    /// Panics if self has no local segment. (The empty segment on its own is the
+    /// `after_post_releases` marker, which means something else.)
+    pub fn local_successor(&self) -> Version {
+        assert!(!self.0.local.is_empty(), "{self} has no local segment");
+        let mut new = self.clone();
+        new.0
+            .local
+            .push(pep440::LocalVersion::StringComponent(String::new()));
+        new
+    }
+
+    // Whether there's an empty local segment anywhere, i.e., this came from
+    // local_successor() or after_post_releases()
+    fn has_empty_local_segment(&self) -> bool {
+        self.0.local.iter().any(
+            |l| matches!(l, pep440::LocalVersion::StringComponent(s) if s.is_empty()),
+        )
+    }
+
+    fn is_after_post_releases(&self) -> bool {
+        self.0.local.len() == 1 && self.0.local == after_post_releases_marker()
+    }
+
+    fn is_infinity(&self) -> bool {
+        // check the epoch first so the common case doesn't need to touch the Lazy
+        self.0.epoch == u32::MAX && self == &*VERSION_INFINITY
+    }
+
+    /// Parses versions from older packages that don't quite follow PEP 440. The strict
+    /// parser already accepts everything PEP 440 says to normalize (a leading "v", any
+    /// letter case, "-"/"_"/"." separators, and spellings like "alpha", "c", or
+    /// "preview"). On top of that, this:
+    ///
+    /// - strips leading and trailing whitespace
+    /// - removes whitespace inside the version ("v 1.0", "1.0 rc1")
+    /// - drops a trailing "final" tag ("1.0.final", "1.0-FINAL")
+    ///
+    /// and then parses the result strictly. Anything else is still an error.
+    pub fn parse_relaxed(s: &str) -> Result<Version> {
+        let cleaned: String = s.chars().filter(|c| !c.is_whitespace()).collect();
+        let cleaned = LEGACY_FINAL.replace(&cleaned, "");
+        Version::try_from(cleaned.as_ref()).wrap_err_with(|| {
+            format!("can't parse {:?} as a version, even leniently", s)
+        })
+    }
+
+    /// True for alpha/beta/rc releases, and also for dev releases (PEP 440 counts
+    /// "1.0.dev1" as a pre-release of "1.0").
+    #[inline]
     pub fn is_prerelease(&self) -> bool {
         self.0.pre.is_some() || self.0.dev.is_some()
     }
 
-    /// Returns the smallest PEP 440 version that is larger than self.
+    #[inline]
+    pub fn is_postrelease(&self) -> bool {
+        self.0.post.is_some()
+    }
+
+    #[inline]
+    pub fn is_devrelease(&self) -> bool {
+        self.0.dev.is_some()
+    }
+
+    #[inline]
+    pub fn epoch(&self) -> u32 {
+        self.0.epoch
+    }
+
+    /// The numeric release tuple, e.g. [1, 2, 3] for "1!1.2.3rc1".
+    #[inline]
+    pub fn release_segments(&self) -> &[u32] {
+        &self.0.release
+    }
+
+    /// The local segments, e.g. [String("ubuntu"), Numeric(7)] for "1.0+ubuntu.7", or
+    /// nothing for a public version. (The range-bound sentinels use an empty string
+    /// segment, which real versions never have.)
+    ///
+    /// PEP 440 orders local versions segment by segment: numeric segments compare as
+    /// numbers, string segments compare lexically, and a numeric segment is greater
+    /// than any string one. If one is a prefix of the other, the shorter one is
+    /// smaller. So:
+    ///
+    ///   1.0+abc < 1.0+abc.1 < 1.0+1 < 1.0+1.0 < 1.0+1.0.0 < 1.0+2 < 1.0+10
+    ///
+    /// Note that unlike the release, trailing zeros matter here: "1.0+1.0" and
+    /// "1.0+1.0.0" are different versions.
+    #[inline]
+    pub fn local_segments(&self) -> &[LocalSegment] {
+        &self.0.local
+    }
+
+    /// Returns a new version with the same epoch as self, the given release segments,
+    /// and no pre, post, dev, or local fields.
+    ///
+    /// Panics if `segments` is empty.
+    pub fn with_release(&self, segments: Vec<u32>) -> Version {
+        assert!(
+            !segments.is_empty(),
+            "versions need at least one release segment"
+        );
+        Version(pep440::Version {
+            epoch: self.0.epoch,
+            release: segments,
+            pre: None,
+            post: None,
+            dev: None,
+            local: vec![],
+        })
+    }
+
+    /// Increments the release segment at `index` (0 = major), drops all the segments
+    /// after it, and clears pre, post, dev, and local; the epoch is kept. So bumping
+    /// index 1 of "1.4.7rc1" gives "1.5". If the release is too short, it's extended
+    /// with zeros first, e.g. bumping index 2 of "1" gives "1.0.1".
+    ///
+    /// Fails if that segment is already u32::MAX, since there's nothing bigger.
+    ///
+    /// (This isn't pubgrub's `Version::bump`, which gives the next possible version.
+    /// Since this one takes precedence for `v.bump(...)`, call that one as
+    /// `pubgrub::version::Version::bump(&v)`.)
+    pub fn bump(&self, index: usize) -> Result<Version> {
+        let mut release = self.0.release.clone();
+        release.resize(std::cmp::max(release.len(), index + 1), 0);
+        release.truncate(index + 1);
+        release[index] = release[index].checked_add(1).ok_or_else(|| {
+            eyre!(
+                "can't bump release segment {} of {}: it's already the maximum",
+                index,
+                self
+            )
+        })?;
+        Ok(self.with_release(release))
+    }
+
+    /// The exclusive upper bound of "~= self": drop the last release segment, bump the
+    /// one before it, and make it a .dev0 so that the next series' pre-releases are
+    /// excluded too. So "2.2" gives "3.dev0", and "1.4.5rc1" gives "1.5.dev0".
+    ///
+    /// Fails if there's only one release segment, since "~= 1" isn't allowed, or if
+    /// the segment to bump is already u32::MAX.
+    pub fn compatible_upper_bound(&self) -> Result<Version> {
+        let len = self.0.release.len();
+        if len < 2 {
+            bail!("~= requires at least two release segments, got '{}'", self);
+        }
+        let mut bound = self.bump(len - 2)?;
+        bound.0.dev = Some(0);
+        Ok(bound)
+    }
+
+    /// Whether "self ~= base" holds: self is at least base, and below
+    /// `base.compatible_upper_bound()`. So 1.4.9 is a compatible release of 1.4, and
+    /// so is 1.9, but 2.0 isn't.
+    ///
+    /// Fails if base has only one release segment.
+    pub fn is_compatible_release_of(&self, base: &Version) -> Result<bool> {
+        Ok(base <= self && self < &base.compatible_upper_bound()?)
+    }
+
+    /// Returns just the epoch and release segments, e.g. "1!2.0rc1.post3+local" ->
+    /// "1!2.0". The pre, post, dev, and local fields are all cleared. (Same as
+    /// 'packaging.version.Version.base_version'.)
+    pub fn base_version(&self) -> Version {
+        self.with_release(self.0.release.clone())
+    }
+
+    /// Returns the version with its local segment cleared, e.g. "1.2.3rc1+cuda" ->
+    /// "1.2.3rc1". All other fields are preserved.
+    pub fn public(&self) -> Version {
+        let mut new = self.clone();
+        new.0.local.clear();
+        new
+    }
+
+    /// Compares versions while ignoring their local segments, so e.g. "1.0+abc" and
+    /// "1.0+xyz" are equal.
+    pub fn cmp_ignore_local(&self, other: &Version) -> std::cmp::Ordering {
+        self.public().cmp(&other.public())
+    }
+
+    /// Returns the smallest public (non-local) PEP 440 version that is larger than
+    /// self. We use this to turn inclusive bounds into the exclusive upper end of a
+    /// half-open range, e.g. "== V" becomes [V, V.next()).
+    ///
+    /// Local versions sort immediately after their public version ("1.0" < "1.0+abc" <
+    /// "1.0.post0.dev0"), and there's no smallest local version larger than a given
+    /// one, so local segments are ignored: "1.0+abc".next() == "1.0".next(). This way
+    /// [V, V.next()) contains V and all of its local variants, which is what PEP 440
+    /// wants for "== V".
     pub fn next(&self) -> Version {
         let mut new = self.clone();
+        new.0.local.clear();
         // The rules are here:
         //
         //   https://www.python.org/dev/peps/pep-0440/#summary-of-permitted-suffixes-and-relative-ordering
@@ -49,7 +339,8 @@ impl Version {
         //   then the next possible version is .dev(N+1)
         //
         // - You can't attach a .postN after a .postN. So if you already have
-        //   a .postN, then the next possible value is .post(N+1).
+        //   a .postN, then the next possible value is .post(N+1).dev0 (since
+        //   .post(N+1).dev0 sorts before .post(N+1)).
         //
         // - You *can* attach a .postN after anything else. And a .devN after that. So
         // to get the next possible value, attach a .post0.dev0.
@@ -57,6 +348,7 @@ impl Version {
             *dev += 1;
         } else if let Some(post) = &mut new.0.post {
             *post += 1;
+            new.0.dev = Some(0);
         } else {
             new.0.post = Some(0);
             new.0.dev = Some(0);
@@ -69,17 +361,112 @@ impl TryFrom<&str> for Version {
     type Error = eyre::Report;
 
     fn try_from(value: &str) -> Result<Self, Self::Error> {
-        pep440::Version::parse(value)
-            .map(|v| Version(v))
-            .ok_or_else(|| eyre!("Failed to parse PEP 440 version {}", value))
+        match pep440::Version::parse(value) {
+            Some(v) => Ok(Version(v)),
+            None => Err(VocabError::InvalidVersion {
+                input: value.into(),
+            })?,
+        }
     }
 }
 
 try_from_str_boilerplate!(Version);
 
+/// For code that already works with the 'pep440' crate directly. No re-parsing
+/// needed in either direction.
+impl From<pep440::Version> for Version {
+    fn from(value: pep440::Version) -> Self {
+        Version(value)
+    }
+}
+
+impl From<Version> for pep440::Version {
+    fn from(value: Version) -> Self {
+        value.0
+    }
+}
+
+impl AsRef<pep440::Version> for Version {
+    fn as_ref(&self) -> &pep440::Version {
+        &self.0
+    }
+}
+
+impl Version {
+    pub fn as_pep440(&self) -> &pep440::Version {
+        &self.0
+    }
+}
+
+/// Always the PEP 440 normalized form, regardless of how the version was spelled when
+/// it was parsed: "V1.0-Alpha1" displays as "1.0a1". We don't keep the original
+/// string around; nothing needs it so far.
 impl Display for Version {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.0)
+        if self.is_infinity() {
+            write!(f, "∞")
+        } else if self.is_after_post_releases() {
+            write!(f, "{}.post∞", self.public())
+        } else {
+            write!(f, "{}", self.0)
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for Version {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        if self.is_infinity() || self.has_empty_local_segment() {
+            return Err(serde::ser::Error::custom(format!(
+                "can't serialize {self}; it's a range bound, not a version"
+            )));
+        }
+        serializer.collect_str(self)
+    }
+}
+
+// Strips everything that can't move a version past an after_post_releases bound. Except
+// that a bare ".devN" (no pre or post) has to stay, because that sorts before all the
+// pre-releases.
+fn without_post_dev_local(v: &Version) -> pep440::Version {
+    let bare_dev = v.0.pre.is_none() && v.0.post.is_none();
+    pep440::Version {
+        post: None,
+        dev: if bare_dev { v.0.dev } else { None },
+        local: vec![],
+        ..v.0.clone()
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        use std::cmp::Ordering::*;
+        match (self.is_infinity(), other.is_infinity()) {
+            (true, true) => Equal,
+            (true, false) => Greater,
+            (false, true) => Less,
+            (false, false) => match (
+                self.is_after_post_releases(),
+                other.is_after_post_releases(),
+            ) {
+                (false, false) => self.0.cmp(&other.0),
+                // If the other version has the same epoch, release, and pre-release as
+                // the bound, it's one of the variants the bound sorts after. Otherwise,
+                // post/dev/local can't affect the result, so ignore them.
+                (self_bound, other_bound) => without_post_dev_local(self)
+                    .cmp(&without_post_dev_local(other))
+                    .then(self_bound.cmp(&other_bound)),
+            },
+        }
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
     }
 }
 
@@ -92,3 +479,456 @@ impl pubgrub::version::Version for Version {
         self.next()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn v(s: &str) -> Version {
+        s.try_into().unwrap()
+    }
+
+    #[test]
+    fn test_base_version_and_public() {
+        let version = v("1!2.0rc1.post3.dev4+cuda.11");
+        assert_eq!(version.base_version().to_string(), "1!2.0");
+        assert_eq!(version.public().to_string(), "1!2.0rc1.post3.dev4");
+
+        assert_eq!(v("1.2.3+cuda").base_version(), v("1.2.3"));
+        assert_eq!(v("1.2.3+cuda").public(), v("1.2.3"));
+        assert_ne!(v("1.2.3+cuda"), v("1.2.3"));
+
+        // no-ops on plain releases
+        assert_eq!(v("1.2.3").base_version().to_string(), "1.2.3");
+        assert_eq!(v("1.2.3").public().to_string(), "1.2.3");
+    }
+
+    #[test]
+    fn test_from_components() {
+        use LocalSegment::*;
+
+        let version = Version::from_components(
+            2,
+            vec![1, 0],
+            Some(PreRelease::A(1)),
+            Some(2),
+            Some(3),
+            vec![StringComponent("abc".into())],
+        )
+        .unwrap();
+        assert_eq!(version.to_string(), "2!1.0a1.post2.dev3+abc");
+        assert_eq!(version, v("2!1.0a1.post2.dev3+abc"));
+
+        let with_local = |local: Vec<LocalSegment>| {
+            Version::from_components(0, vec![1], None, None, None, local)
+        };
+        let version =
+            with_local(vec![StringComponent("Ubuntu".into()), NumericComponent(7)])
+                .unwrap();
+        assert_eq!(version.to_string(), "1+ubuntu.7");
+        assert_eq!(version, v("1+ubuntu.7"));
+
+        for bad in ["", "a.b", "a-b", "é", "12"] {
+            assert!(with_local(vec![StringComponent(bad.into())]).is_err());
+        }
+        assert!(Version::from_components(0, vec![], None, None, None, vec![]).is_err());
+    }
+
+    #[test]
+    fn test_local_segments() {
+        use LocalSegment::*;
+
+        assert_eq!(
+            v("1.0+Ubuntu-7.b").local_segments(),
+            [
+                StringComponent("ubuntu".into()),
+                NumericComponent(7),
+                StringComponent("b".into()),
+            ]
+        );
+        assert!(v("1.0").local_segments().is_empty());
+
+        let ordered = [
+            "1.0",
+            "1.0+abc",
+            "1.0+abc.1",
+            "1.0+abd",
+            "1.0+1",
+            "1.0+1.abc",
+            "1.0+1.0",
+            "1.0+1.0.0",
+            "1.0+2",
+            "1.0+10",
+            "1.0.post1",
+        ];
+        for pair in ordered.windows(2) {
+            assert!(v(pair[0]) < v(pair[1]), "{} < {}", pair[0], pair[1]);
+        }
+        assert_ne!(v("1.0+1.0"), v("1.0+1.0.0"));
+        // segments compare as numbers, so leading zeros don't count
+        assert_eq!(v("1.0+01"), v("1.0+1"));
+        assert!(NumericComponent(0) > StringComponent("zzz".into()));
+    }
+
+    #[test]
+    fn test_pep440_conversions() {
+        let raw = pep440::Version::parse("1!2.0rc1.post3+local.7").unwrap();
+        let version: Version = raw.clone().into();
+        assert_eq!(version, v("1!2.0rc1.post3+local.7"));
+        assert_eq!(version.as_pep440(), &raw);
+        let as_ref: &pep440::Version = version.as_ref();
+        assert_eq!(as_ref, &raw);
+        let back: pep440::Version = version.into();
+        assert_eq!(back, raw);
+        assert_eq!(Version::from(back).to_string(), "1!2.0rc1.post3+local.7");
+    }
+
+    #[test]
+    fn test_release_segments() {
+        let version = v("1!1.2.3rc1.post2.dev3+local");
+        assert_eq!(version.release_segments(), &[1, 2, 3]);
+
+        let new = version.with_release(vec![4, 5]);
+        assert_eq!(new.release_segments(), &[4, 5]);
+        assert_eq!(new.to_string(), "1!4.5");
+        assert!(new.0.pre.is_none());
+        assert!(new.0.post.is_none());
+        assert!(new.0.dev.is_none());
+        assert!(new.0.local.is_empty());
+    }
+
+    #[test]
+    fn test_bump() {
+        let bump = |s: &str, index| v(s).bump(index).unwrap().to_string();
+        assert_eq!(bump("1.4.7", 0), "2");
+        assert_eq!(bump("1.4.7", 1), "1.5");
+        assert_eq!(bump("1.4.7", 2), "1.4.8");
+        // out of range extends with zeros
+        assert_eq!(bump("1.4.7", 4), "1.4.7.0.1");
+        assert_eq!(bump("1", 2), "1.0.1");
+        // suffixes are cleared, epoch is kept
+        assert_eq!(bump("2!1.4.7rc1.post2.dev3+local", 1), "2!1.5");
+        assert!(v("1.4.7").bump(2).unwrap() > v("1.4.7"));
+
+        // a segment that's already u32::MAX can't go any higher
+        let max = v("1.4294967295.3");
+        let err = max.bump(1).unwrap_err().to_string();
+        assert!(
+            err.contains("release segment 1 of 1.4294967295.3"),
+            "{}",
+            err
+        );
+        assert_eq!(max.bump(0).unwrap().to_string(), "2");
+        assert_eq!(max.bump(2).unwrap().to_string(), "1.4294967295.4");
+    }
+
+    #[test]
+    fn test_compatible_upper_bound() {
+        let bound = |s: &str| Version::try_from(s).unwrap().compatible_upper_bound();
+        assert_eq!(bound("2.2").unwrap(), "3.dev0".try_into().unwrap());
+        assert_eq!(bound("1.4.5").unwrap(), "1.5.dev0".try_into().unwrap());
+        // suffixes on the original don't matter, but the epoch is kept
+        assert_eq!(
+            bound("1.4.5rc1.post2+local").unwrap(),
+            "1.5.dev0".try_into().unwrap()
+        );
+        assert_eq!(bound("2!1.0").unwrap(), "2!2.dev0".try_into().unwrap());
+
+        assert_eq!(
+            bound("1").unwrap_err().to_string(),
+            "~= requires at least two release segments, got '1'"
+        );
+    }
+
+    #[test]
+    fn test_is_compatible_release_of() {
+        let compat = |v: &str, base: &str| {
+            let v: Version = v.try_into().unwrap();
+            v.is_compatible_release_of(&base.try_into().unwrap())
+        };
+        assert!(compat("1.4.9", "1.4").unwrap());
+        assert!(compat("1.4", "1.4").unwrap());
+        assert!(compat("1.9", "1.4").unwrap());
+        assert!(!compat("1.4.9", "1.5").unwrap());
+        assert!(!compat("2.0", "1.4").unwrap());
+        assert!(!compat("2.0a1", "1.4").unwrap());
+        assert!(!compat("1.3.99", "1.4").unwrap());
+        // with more segments, the band is narrower
+        assert!(compat("1.4.9", "1.4.5").unwrap());
+        assert!(!compat("1.5.0", "1.4.5").unwrap());
+        assert!(compat("1", "1").is_err());
+        // agrees with the ~= specifier
+        for (v, base) in [("1.4.9", "1.4"), ("1.5", "1.4.5"), ("2.0.dev1", "1.4")] {
+            let spec = CompareOp::Compatible;
+            assert_eq!(
+                compat(v, base).unwrap(),
+                spec.matches(&v.try_into().unwrap(), &base.try_into().unwrap())
+                    .unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_next() {
+        assert_eq!(v("1.0").next().to_string(), "1.0.post0.dev0");
+        assert_eq!(v("1.0a1").next().to_string(), "1.0a1.post0.dev0");
+        assert_eq!(v("1.0+abc").next().to_string(), "1.0.post0.dev0");
+        assert_eq!(v("1.0.dev3").next().to_string(), "1.0.dev4");
+        assert_eq!(v("1.0.post1").next().to_string(), "1.0.post2.dev0");
+        assert_eq!(v("1.0.post1.dev1").next().to_string(), "1.0.post1.dev2");
+        assert_eq!(v("1!1.0").next().to_string(), "1!1.0.post0.dev0");
+
+        for version in ["1.0", "1.0a1", "1.0+abc", "1.0.dev3", "1.0.post1"] {
+            let version = v(version);
+            assert!(version.next() > version);
+            assert!(version.next() > version.public());
+        }
+        assert!(v("1.0+abc.99") < v("1.0").next());
+        // .next() is the smallest larger version, so nothing can fit between them
+        assert!(v("1.0.post2.dev0") >= v("1.0.post1").next());
+
+        // == matches the version and its local variants, and nothing else
+        let specs: Specifiers = "==1.0".try_into().unwrap();
+        for (version, expected) in [
+            ("1.0", true),
+            ("1.0.0", true),
+            ("1.0+abc", true),
+            ("1.0.post0", false),
+            ("1.0.1", false),
+        ] {
+            assert_eq!(specs.satisfied_by(&v(version)).unwrap(), expected);
+        }
+        let specs: Specifiers = "==1.0.post1".try_into().unwrap();
+        assert!(!specs
+            .satisfied_by_with_prereleases(&v("1.0.post2.dev0"), true)
+            .unwrap());
+    }
+
+    #[test]
+    fn test_ordering() {
+        use std::cmp::Ordering;
+
+        let expected: Vec<Version> = [
+            "0.9",
+            "1.0.dev1",
+            "1.0a1.dev1",
+            "1.0a1",
+            "1.0a1.post1",
+            "1.0b1",
+            "1.0rc1",
+            "1.0",
+            "1.0+abc",
+            "1.0+abc.1",
+            "1.0+abc.2",
+            "1.0.post1.dev1",
+            "1.0.post1",
+            "1.0.1",
+            "1.1.dev1",
+            "2.0",
+            "1!0.1",
+        ]
+        .into_iter()
+        .map(v)
+        .collect();
+        for seed in 0..10 {
+            let rng = fastrand::Rng::with_seed(seed);
+            let mut shuffled = expected.clone();
+            rng.shuffle(&mut shuffled);
+            shuffled.sort();
+            assert_eq!(shuffled, expected);
+        }
+
+        assert_eq!(v("1.0").cmp(&v("1.0.0")), Ordering::Equal);
+        assert_eq!(v("1.0+abc").cmp(&v("1.0+xyz")), Ordering::Less);
+        assert_eq!(
+            v("1.0+abc").cmp_ignore_local(&v("1.0+xyz")),
+            Ordering::Equal
+        );
+        assert_eq!(v("1.0+abc").cmp_ignore_local(&v("1.0")), Ordering::Equal);
+        assert_eq!(
+            v("1.0+abc").cmp_ignore_local(&v("1.0.post1")),
+            Ordering::Less
+        );
+        assert_eq!(
+            v("2.0+abc").cmp_ignore_local(&v("1.0+xyz")),
+            Ordering::Greater
+        );
+    }
+
+    #[test]
+    fn test_epoch() {
+        assert_eq!(v("1.0").epoch(), 0);
+        assert_eq!(v("2!1.0").epoch(), 2);
+    }
+
+    #[test]
+    fn test_release_kind_accessors() {
+        let cases = [
+            // version, pre, post, dev
+            ("1.0", false, false, false),
+            ("1.0a1", true, false, false),
+            ("1.0rc2", true, false, false),
+            ("1.0.post1", false, true, false),
+            ("1.0.dev1", true, false, true),
+            ("1.0a1.dev1", true, false, true),
+            ("1.0.post1.dev1", true, true, true),
+            ("1.0+local", false, false, false),
+        ];
+        for (version, pre, post, dev) in cases {
+            let version = v(version);
+            assert_eq!(version.is_prerelease(), pre, "{version}");
+            assert_eq!(version.is_postrelease(), post, "{version}");
+            assert_eq!(version.is_devrelease(), dev, "{version}");
+        }
+    }
+
+    #[test]
+    fn test_display_is_normalized() {
+        for (input, expected) in [
+            ("1.0alpha1", "1.0a1"),
+            ("1.0-beta.2", "1.0b2"),
+            ("1.0c1", "1.0rc1"),
+            ("1.0-pre3", "1.0rc3"),
+            ("v1.0", "1.0"),
+            ("01.002", "1.2"),
+            ("1.0-post", "1.0.post0"),
+            ("1.0.rev4", "1.0.post4"),
+            ("1.0-1", "1.0.post1"),
+            ("1.0-dev", "1.0.dev0"),
+            ("0!1.0", "1.0"),
+            ("1.0+Ubuntu-1_2", "1.0+ubuntu.1.2"),
+            // trailing zeros are significant for display, even though they don't
+            // affect comparisons
+            ("1.0.0", "1.0.0"),
+        ] {
+            assert_eq!(v(input).to_string(), expected, "{input}");
+            // and the normalized form parses back to itself
+            assert_eq!(v(expected).to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_parse_relaxed() {
+        for (input, expected) in [
+            ("v1.0", "1.0"),
+            ("1.0-alpha", "1.0a0"),
+            ("1.0RC1", "1.0rc1"),
+            ("  1.0\n", "1.0"),
+            ("v 1.0", "1.0"),
+            ("1.0 rc1", "1.0rc1"),
+            ("1.0.final", "1.0"),
+            ("1.0-FINAL", "1.0"),
+        ] {
+            assert_eq!(Version::parse_relaxed(input).unwrap().to_string(), expected);
+        }
+        // the strict parser doesn't do any of the extra cleanups
+        assert!(Version::try_from(" 1.0").is_err());
+        assert!(Version::try_from("1.0.final").is_err());
+
+        assert!(Version::parse_relaxed("1.0.0-SNAPSHOT").is_err());
+        assert!(Version::parse_relaxed("final").is_err());
+    }
+
+    #[test]
+    fn test_arbitrary_version_roundtrip() {
+        use crate::vocab::arbitrary::arbitrary_version;
+
+        for seed in 0..1000 {
+            let version = arbitrary_version(&fastrand::Rng::with_seed(seed));
+            assert_eq!(v(&version.to_string()), version, "{version}");
+        }
+    }
+
+    #[test]
+    fn test_sentinels() {
+        let zero = &*VERSION_ZERO;
+        let infinity = &*VERSION_INFINITY;
+        assert!(zero.is_sentinel());
+        assert!(infinity.is_sentinel());
+        assert!(zero < infinity);
+
+        for version in [
+            "0.dev1",
+            "0a0.dev0",
+            "0",
+            "1.0",
+            "1.0+local",
+            "99999!99999.99999.post99999",
+            // larger than all of INFINITY's fields, but still less than it
+            "4294967295!4294967295.4294967295.4294967295.4294967295",
+        ] {
+            let version = v(version);
+            assert!(!version.is_sentinel(), "{version}");
+            assert!(zero < &version, "{version}");
+            assert!(&version < infinity, "{version}");
+            assert_eq!(version.cmp(infinity), std::cmp::Ordering::Less);
+        }
+        // nothing sorts before zero
+        assert_eq!(v("0.0.dev0"), *zero);
+
+        let bound = v("1.0").after_post_releases();
+        assert!(bound.is_sentinel());
+        for below in [
+            "1.0",
+            "1.0.dev1",
+            "1.0+abc",
+            "1.0.post4294967295",
+            "0.9.post1",
+        ] {
+            assert!(v(below) < bound, "{below}");
+        }
+        for above in ["1.0.0.1.dev0", "1.1a1", "2!0"] {
+            assert!(v(above) > bound, "{above}");
+        }
+        let pre_bound = v("1.0a1").after_post_releases();
+        assert!(v("1.0.dev1") < pre_bound);
+        assert!(v("1.0a1.post4294967295") < pre_bound);
+        assert!(pre_bound < v("1.0a2.dev0"));
+        assert!(pre_bound < bound);
+        assert_eq!(
+            bound.cmp(&v("1.0.0").after_post_releases()),
+            std::cmp::Ordering::Equal
+        );
+        assert_eq!(bound.to_string(), "1.0.post∞");
+        #[cfg(feature = "serde")]
+        assert!(serde_json::to_string(&bound).is_err());
+
+        let local = v("1.0+abc");
+        let successor = local.local_successor();
+        assert!(successor.is_sentinel());
+        assert!(!local.is_sentinel());
+        assert!(local < successor);
+        for above in [
+            "1.0+abc.0",
+            "1.0+abc.a",
+            "1.0+abc.0a",
+            "1.0+abd",
+            "1.0.post0.dev0",
+        ] {
+            assert!(successor < v(above), "{above}");
+        }
+        #[cfg(feature = "serde")]
+        assert!(serde_json::to_string(&successor).is_err());
+
+        assert_eq!(zero.to_string(), "0.dev0");
+        assert_eq!(infinity.to_string(), "∞");
+        #[cfg(feature = "serde")]
+        {
+            assert!(serde_json::to_string(infinity).is_err());
+            assert_eq!(serde_json::to_string(zero).unwrap(), r#""0.dev0""#);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_version_serde() {
+        let version = v("1!2.0RC1.post3-dev4+Local.7");
+        let json = serde_json::to_string(&version).unwrap();
+        assert_eq!(json, r#""1!2.0rc1.post3.dev4+local.7""#);
+        let roundtrip: Version = serde_json::from_str(&json).unwrap();
+        assert_eq!(roundtrip, version);
+
+        let bad: serde_json::Result<Version> = serde_json::from_str(r#""1.0-garbage""#);
+        assert!(bad.is_err());
+    }
+}